@@ -0,0 +1,70 @@
+use othello_bot::pattern::PHASES;
+use othello_bot::training::{fit, format_sample, generate_samples, load_samples};
+use std::env;
+use std::ops::RangeInclusive;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// Appends solved random positions to the sample file.
+fn generate(path: &Path, count: usize, empties: RangeInclusive<u32>) {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("Could not open samples");
+    let mut writer = BufWriter::new(file);
+
+    for (i, sample) in generate_samples(count, empties).iter().enumerate() {
+        writeln!(writer, "{}", format_sample(sample)).expect("Could not write samples");
+
+        if (i + 1) % 1000 == 0 {
+            println!("{:9} samples", i + 1);
+        }
+    }
+
+    writer.flush().expect("Could not write samples");
+}
+
+fn train(samples_path: &Path, weights_path: &Path, epochs: u32) {
+    let samples = load_samples(samples_path).expect("Could not load samples");
+    println!("Loaded {} samples from {}", samples.len(), samples_path.display());
+
+    let weights = fit(&samples, epochs, |epoch, error| {
+        println!("Epoch {:4}: error {:6.3} discs", epoch + 1, error);
+    });
+
+    let untrained: Vec<String> = (0..PHASES)
+        .filter(|phase| !weights.is_trained(*phase))
+        .map(|phase| phase.to_string())
+        .collect();
+    if !untrained.is_empty() {
+        println!("Phases without samples use the simple evaluation: {}", untrained.join(" "));
+    }
+
+    weights.save(weights_path).expect("Could not save weights");
+    println!("Saved weights to {}", weights_path.display());
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let arg = |i: usize| args.get(i).map(|arg| arg.as_str());
+
+    match (arg(1), arg(2)) {
+        (Some("generate"), Some(path)) => {
+            let count = arg(3).map_or(10000, |count| count.parse().expect("Invalid count"));
+            let parse_empties = |empties: &str| empties.parse().expect("Invalid empties");
+            let min_empties = arg(4).map_or(14, parse_empties);
+            let max_empties = arg(5).map_or(min_empties, parse_empties);
+            generate(Path::new(path), count, min_empties..=max_empties)
+        }
+        (Some("fit"), Some(path)) => {
+            let weights_path = arg(3).unwrap_or("weights.bin");
+            let epochs = arg(4).map_or(100, |epochs| epochs.parse().expect("Invalid epochs"));
+            train(Path::new(path), Path::new(weights_path), epochs)
+        }
+        _ => eprintln!(
+            "Usage: train [generate <samples> [count] [min empties] [max empties] | fit <samples> [weights] [epochs]]"
+        ),
+    }
+}
@@ -18,8 +18,71 @@ pub fn nonzero(x: u64x4) -> u64x4 {
     one & u64x4::from_cast(mask)
 }
 
+pub fn flip_vertical(x: u64) -> u64 {
+    x.swap_bytes()
+}
+
+pub fn flip_horizontal(mut x: u64) -> u64 {
+    x = ((x >> 1) & 0x5555555555555555) | ((x & 0x5555555555555555) << 1);
+    x = ((x >> 2) & 0x3333333333333333) | ((x & 0x3333333333333333) << 2);
+    x = ((x >> 4) & 0x0F0F0F0F0F0F0F0F) | ((x & 0x0F0F0F0F0F0F0F0F) << 4);
+    x
+}
+
+pub fn flip_diagonal(mut x: u64) -> u64 {
+    let mut t = 0x0F0F0F0F00000000 & (x ^ (x << 28));
+    x ^= t ^ (t >> 28);
+    t = 0x3333000033330000 & (x ^ (x << 14));
+    x ^= t ^ (t >> 14);
+    t = 0x5500550055005500 & (x ^ (x << 7));
+    x ^= t ^ (t >> 7);
+    x
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{flip_diagonal, flip_horizontal, flip_vertical};
+
     // TODO test fn nonzero(x: u64x4) -> u64x4
     // TODO test fn upper_bit(mut x: u64x4) -> u64x4
+
+    fn naive_map(x: u64, map: fn(usize, usize) -> (usize, usize)) -> u64 {
+        let mut result = 0;
+        for index in 0..64 {
+            if (x >> index) & 1 == 1 {
+                let (mx, my) = map(index % 8, index / 8);
+                result |= 1 << (8 * my + mx);
+            }
+        }
+        result
+    }
+
+    fn test_values() -> Vec<u64> {
+        let mut values = vec![0, 0xFFFFFFFFFFFFFFFF, 0x0123456789ABCDEF, 0x8000000000000001];
+        for index in 0..64 {
+            values.push(1 << index);
+        }
+        values
+    }
+
+    #[test]
+    fn test_flip_vertical() {
+        for x in test_values() {
+            assert_eq!(naive_map(x, |x, y| (x, 7 - y)), flip_vertical(x));
+        }
+    }
+
+    #[test]
+    fn test_flip_horizontal() {
+        for x in test_values() {
+            assert_eq!(naive_map(x, |x, y| (7 - x, y)), flip_horizontal(x));
+        }
+    }
+
+    #[test]
+    fn test_flip_diagonal() {
+        for x in test_values() {
+            assert_eq!(naive_map(x, |x, y| (y, x)), flip_diagonal(x));
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use crate::bits::{nonzero, upper_bit};
+use crate::bits::{flip_diagonal, flip_horizontal, flip_vertical, nonzero, upper_bit};
 use packed_simd::*;
 use rand::Rng;
 use std::mem;
@@ -15,6 +15,51 @@ impl PartialEq for Board {
     }
 }
 
+// One of the 8 board symmetries: an optional horizontal mirror, an optional
+// vertical mirror and then an optional flip along the a1-h8 diagonal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transform(u8);
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform(0);
+
+    pub fn all() -> impl Iterator<Item = Transform> {
+        (0..8).map(Transform)
+    }
+
+    pub fn apply(self, mut bits: u64) -> u64 {
+        if self.0 & 1 != 0 {
+            bits = flip_horizontal(bits);
+        }
+        if self.0 & 2 != 0 {
+            bits = flip_vertical(bits);
+        }
+        if self.0 & 4 != 0 {
+            bits = flip_diagonal(bits);
+        }
+        bits
+    }
+
+    pub fn apply_move(self, index: usize) -> usize {
+        self.apply(1 << index).trailing_zeros() as usize
+    }
+
+    pub fn inverse(self) -> Transform {
+        // Mirroring after a diagonal flip equals a diagonal flip after the other mirror.
+        match self.0 {
+            5 => Transform(6),
+            6 => Transform(5),
+            _ => self,
+        }
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Board::new()
+    }
+}
+
 impl Board {
     pub fn new() -> Board {
         Board {
@@ -23,6 +68,21 @@ impl Board {
         }
     }
 
+    pub fn from_bitboards(me: u64, opp: u64) -> Board {
+        if me & opp != 0 {
+            panic!("Two discs on one square");
+        }
+        Board { me, opp }
+    }
+
+    pub fn me(&self) -> u64 {
+        self.me
+    }
+
+    pub fn opp(&self) -> u64 {
+        self.opp
+    }
+
     pub fn new_random(discs: u32) -> Board {
         if discs < 4 || discs > 64 {
             panic!("Invalid amount of discs");
@@ -117,7 +177,7 @@ impl Board {
         return res.or();
     }
 
-    fn flip(&self, pos: usize) -> u64 {
+    pub fn flip(&self, pos: usize) -> u64 {
         let p = u64x4::new(self.me, self.me, self.me, self.me);
         let o = u64x4::new(self.opp, self.opp, self.opp, self.opp);
         let omask = u64x4::new(
@@ -197,6 +257,44 @@ impl Board {
         children
     }
 
+    pub fn children_with_moves(&self) -> Vec<(usize, Board)> {
+        let mut moves = self.moves();
+        let mut children: Vec<(usize, Board)> = Vec::new();
+
+        while moves != 0 {
+            let index = moves.trailing_zeros() as usize;
+            children.push((index, self.do_move(index)));
+            moves &= !(1 << index)
+        }
+        children
+    }
+
+    pub fn hash_key(&self) -> u64 {
+        let mut key = self.me.wrapping_mul(0x9E3779B97F4A7C15);
+        key ^= self.opp.rotate_left(32).wrapping_mul(0xC2B2AE3D27D4EB4F);
+        key ^ (key >> 29)
+    }
+
+    pub fn transform(&self, transform: Transform) -> Board {
+        Board {
+            me: transform.apply(self.me),
+            opp: transform.apply(self.opp),
+        }
+    }
+
+    pub fn normalize(&self) -> (Board, Transform) {
+        let mut best = (self.clone(), Transform::IDENTITY);
+
+        for transform in Transform::all().skip(1) {
+            let board = self.transform(transform);
+            if (board.me, board.opp) < (best.0.me, best.0.opp) {
+                best = (board, transform);
+            }
+        }
+
+        best
+    }
+
     pub fn exact_score(&self) -> i32 {
         let me_count = self.me.count_ones() as i32;
         let opp_count = self.opp.count_ones() as i32;
@@ -218,6 +316,14 @@ impl Board {
         (self.me | self.opp).count_ones()
     }
 
+    pub fn empties(&self) -> u64 {
+        !(self.me | self.opp)
+    }
+
+    pub fn count_empties(&self) -> u32 {
+        self.empties().count_ones()
+    }
+
     pub fn corner_difference(&self) -> i32 {
         let corner_mask = 1 << 0 | 1 << 7 | 1 << 56 | 1 << 63;
         let me_corners = (self.me & corner_mask).count_ones() as i32;
@@ -252,12 +358,86 @@ impl Board {
 
         me_potential_move_count - opp_potential_moves_count
     }
+
+    // Empty squares in quadrants with an odd number of empty squares. Moving there first
+    // tends to leave us the last move in every region.
+    pub fn parity_squares(&self) -> u64 {
+        let quadrants = [
+            0x0000_0000_0F0F_0F0F,
+            0x0000_0000_F0F0_F0F0,
+            0x0F0F_0F0F_0000_0000,
+            0xF0F0_F0F0_0000_0000,
+        ];
+        let empties = self.empties();
+
+        quadrants
+            .iter()
+            .map(|quadrant| empties & quadrant)
+            .filter(|empty| empty.count_ones() % 2 == 1)
+            .fold(0, |parity, empty| parity | empty)
+    }
+
+    // Discs of both players that can never be flipped. A disc is stable when along each
+    // of the four lines through it, the line is filled or a neighbour on that line is
+    // outside the board or a stable disc of the same colour.
+    pub fn stable_discs(&self) -> u64 {
+        let left_mask = 0x7F7F7F7F7F7F7F7F;
+        let right_mask = 0xFEFEFEFEFEFEFEFE;
+        let all_mask = 0xFFFFFFFFFFFFFFFF;
+
+        let shift = u64x4::new(9, 8, 7, 1);
+
+        let left_shift_mask = u64x4::new(left_mask, all_mask, right_mask, left_mask);
+        let right_shift_mask = u64x4::new(right_mask, all_mask, left_mask, right_mask);
+
+        // squares without a neighbour on the side the shifts come from
+        let a_file = 0x0101010101010101;
+        let h_file = 0x8080808080808080;
+        let first_rank = 0x00000000000000FF;
+        let last_rank = 0xFF00000000000000;
+        let left_border = u64x4::new(a_file | first_rank, first_rank, h_file | first_rank, a_file);
+        let right_border = u64x4::new(h_file | last_rank, last_rank, a_file | last_rank, h_file);
+
+        let occupied = u64x4::splat(self.me | self.opp);
+        let mut left_filled = occupied & left_border;
+        let mut right_filled = occupied & right_border;
+        for _ in 0..7 {
+            left_filled |= ((left_filled & left_shift_mask) << shift) & occupied;
+            right_filled |= ((right_filled & right_shift_mask) >> shift) & occupied;
+        }
+        let protected = (left_filled & right_filled) | left_border | right_border;
+
+        let mut stable = 0;
+        loop {
+            let mut next = 0;
+            for discs in [self.me, self.opp].iter() {
+                let anchors = u64x4::splat(stable & discs);
+                let lines = protected
+                    | ((anchors & left_shift_mask) << shift)
+                    | ((anchors & right_shift_mask) >> shift);
+                next |= discs & lines.and();
+            }
+
+            if next == stable {
+                return stable;
+            }
+            stable = next;
+        }
+    }
+
+    pub fn stable_discs_difference(&self) -> i32 {
+        let stable = self.stable_discs();
+        let me_stable = (self.me & stable).count_ones() as i32;
+        let opp_stable = (self.opp & stable).count_ones() as i32;
+
+        me_stable - opp_stable
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
-    use super::Board;
+    use super::{Board, Transform};
     use std::collections::HashSet;
     use std::iter::FromIterator;
 
@@ -407,6 +587,56 @@ pub mod tests {
             0
         }
 
+        fn naive_stable_discs(&self) -> u64 {
+            let occupied = self.me | self.opp;
+            let square = |x: i32, y: i32| 1u64 << (8 * y + x);
+            let on_board = |x: i32, y: i32| (0..8).contains(&x) && (0..8).contains(&y);
+
+            let mut stable = 0;
+            loop {
+                let mut next = 0;
+                for index in 0..64 {
+                    let (x, y) = (index % 8, index / 8);
+                    let discs = if (self.me >> index) & 1 == 1 {
+                        self.me
+                    } else if (self.opp >> index) & 1 == 1 {
+                        self.opp
+                    } else {
+                        continue;
+                    };
+
+                    let mut is_stable = true;
+                    for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)].iter() {
+                        let mut filled = true;
+                        for sign in [-1, 1].iter() {
+                            let (mut cx, mut cy) = (x + sign * dx, y + sign * dy);
+                            while on_board(cx, cy) {
+                                filled &= occupied & square(cx, cy) != 0;
+                                cx += sign * dx;
+                                cy += sign * dy;
+                            }
+                        }
+
+                        let anchored = [-1, 1].iter().any(|sign| {
+                            let (nx, ny) = (x + sign * dx, y + sign * dy);
+                            !on_board(nx, ny) || stable & discs & square(nx, ny) != 0
+                        });
+
+                        is_stable &= filled || anchored;
+                    }
+
+                    if is_stable {
+                        next |= square(x, y);
+                    }
+                }
+
+                if next == stable {
+                    return stable;
+                }
+                stable = next;
+            }
+        }
+
         fn naive_corner_difference(&self) -> i32 {
             let indexes = vec![0, 7, 56, 63];
 
@@ -466,6 +696,44 @@ pub mod tests {
             surround_opp & !(self.me | self.opp)
         }
 
+        fn naive_transform(&self, transform: Transform) -> Board {
+            let mut board = Board { me: 0, opp: 0 };
+            for index in 0..64 {
+                let mut x = index % 8;
+                let mut y = index / 8;
+                if transform.0 & 1 != 0 {
+                    x = 7 - x;
+                }
+                if transform.0 & 2 != 0 {
+                    y = 7 - y;
+                }
+                if transform.0 & 4 != 0 {
+                    std::mem::swap(&mut x, &mut y);
+                }
+                board.me |= ((self.me >> index) & 1) << (8 * y + x);
+                board.opp |= ((self.opp >> index) & 1) << (8 * y + x);
+            }
+            board
+        }
+
+        fn naive_parity_squares(&self) -> u64 {
+            let mut counts = [0; 4];
+            for index in 0..64 {
+                if (self.me | self.opp) & (1 << index) == 0 {
+                    counts[2 * (index / 32) + (index % 8) / 4] += 1;
+                }
+            }
+
+            let mut parity = 0;
+            for index in 0..64 {
+                let empty = (self.me | self.opp) & (1 << index) == 0;
+                if empty && counts[2 * (index / 32) + (index % 8) / 4] % 2 == 1 {
+                    parity |= 1 << index;
+                }
+            }
+            parity
+        }
+
         fn naive_potential_moves_difference(&self) -> i32 {
             let me_potential_moves_count = self.naive_potential_moves().count_ones() as i32;
             let mut clone = self.clone();
@@ -502,6 +770,14 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_board_count_empties() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            assert_eq!(64 - board.naive_count_discs(), board.count_empties());
+        }
+    }
+
     #[test]
     fn test_board_switch_turn() {
         let boards = generate_test_boards();
@@ -570,6 +846,18 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_board_children_with_moves() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            let children = board.children_with_moves();
+            assert_eq!(children.len(), board.children().len());
+            for (index, child) in children.iter() {
+                assert_eq!(board.do_move(*index), *child);
+            }
+        }
+    }
+
     #[test]
     fn test_board_exact_score() {
         let boards = generate_test_boards();
@@ -596,4 +884,104 @@ pub mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_board_transform() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            for transform in Transform::all() {
+                let transformed = board.transform(transform);
+                assert_eq!(board.naive_transform(transform), transformed);
+                assert_eq!(*board, transformed.transform(transform.inverse()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_transform_move() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            for transform in Transform::all() {
+                let transformed = board.transform(transform);
+                for (index, child) in board.children_with_moves() {
+                    let transformed_index = transform.apply_move(index);
+                    assert_eq!(transform.inverse().apply_move(transformed_index), index);
+                    assert_eq!(
+                        child.transform(transform),
+                        transformed.do_move(transformed_index)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_normalize() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            let (normalized, transform) = board.normalize();
+            assert_eq!(normalized, board.transform(transform));
+            assert_eq!(*board, normalized.transform(transform.inverse()));
+
+            for symmetry in Transform::all() {
+                assert_eq!(normalized, board.transform(symmetry).normalize().0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_parity_squares() {
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            assert_eq!(board.naive_parity_squares(), board.parity_squares());
+        }
+    }
+
+    // Plays random games to the end and checks that no disc reported stable changes colour.
+    fn check_stable_discs(board: &Board, stable: u64) {
+        let me = board.me() & stable;
+        let opp = board.opp() & stable;
+
+        for _ in 0..10 {
+            let mut current = board.clone();
+            let mut flipped = false;
+
+            loop {
+                let (current_me, current_opp) = if flipped {
+                    (current.opp(), current.me())
+                } else {
+                    (current.me(), current.opp())
+                };
+                assert_eq!(me, current_me & stable);
+                assert_eq!(opp, current_opp & stable);
+
+                if !current.has_moves() {
+                    current.switch_turn();
+                    flipped = !flipped;
+                    if !current.has_moves() {
+                        break;
+                    }
+                }
+                current = current.do_random_move();
+                flipped = !flipped;
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_stable_discs() {
+        assert_eq!(0, Board::new().stable_discs());
+
+        let boards = generate_test_boards();
+        for board in boards.iter() {
+            let stable = board.stable_discs();
+            assert_eq!(board.naive_stable_discs(), stable);
+            assert_eq!(0, stable & board.empties());
+            check_stable_discs(board, stable);
+
+            let me_stable = (board.me() & stable).count_ones() as i32;
+            let opp_stable = (board.opp() & stable).count_ones() as i32;
+            assert_eq!(me_stable - opp_stable, board.stable_discs_difference());
+        }
+    }
 }
@@ -0,0 +1,545 @@
+use crate::board::Board;
+use crate::limits::Limits;
+use crate::pool::BotPool;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::slice;
+
+const MAGIC: &[u8; 8] = b"OTHBOOK\0";
+const VERSION: u32 = 1;
+const RECORD_SIZE: usize = 8 + 8 + 4 + 1 + 1 + 1;
+const NO_MOVE: u8 = 0xFF;
+
+// Positions evaluated per worker in one batch, at least one leaf is expanded per root.
+const POSITIONS_PER_WORKER: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    // negamax score for the side to move
+    score: i32,
+    // search depth of the evaluation of this position
+    depth: u32,
+    // best move in normalized coordinates, only known for expanded positions
+    best_move: Option<usize>,
+    expanded: bool,
+    // lowest summed score loss to an unexpanded position below this one, none when
+    // nothing below is left to expand; not saved but recomputed on load
+    leaf_loss: Option<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookEntry {
+    pub score: i32,
+    pub depth: u32,
+    pub best_move: Option<usize>,
+}
+
+pub struct Book {
+    nodes: HashMap<Board, Node>,
+    // expanded positions leading to each position, used to pass score changes upwards
+    parents: HashMap<Board, Vec<Board>>,
+}
+
+impl Default for Book {
+    fn default() -> Self {
+        Book::new()
+    }
+}
+
+impl Book {
+    pub fn new() -> Book {
+        Book {
+            nodes: HashMap::new(),
+            parents: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn score(&self, board: &Board) -> Option<i32> {
+        self.nodes.get(&board.normalize().0).map(|node| node.score)
+    }
+
+    // The best move is translated back to the orientation of the given board.
+    pub fn lookup(&self, board: &Board) -> Option<BookEntry> {
+        let (normalized, transform) = board.normalize();
+        let node = self.nodes.get(&normalized)?;
+
+        Some(BookEntry {
+            score: node.score,
+            depth: node.depth,
+            best_move: node
+                .best_move
+                .map(|index| transform.inverse().apply_move(index)),
+        })
+    }
+
+    // Positions to search after this one, a passed board if the player to move has to pass.
+    fn successors(board: &Board) -> Vec<(Option<usize>, Board)> {
+        let children = board.children_with_moves();
+        if !children.is_empty() {
+            return children
+                .into_iter()
+                .map(|(index, child)| (Some(index), child))
+                .collect();
+        }
+
+        let mut passed = board.clone();
+        passed.switch_turn();
+        if passed.has_moves() {
+            return vec![(None, passed)];
+        }
+        vec![]
+    }
+
+    // Adds the positions missing from the book, evaluating them in one batch.
+    fn add_all(&mut self, boards: &[Board], pool: &mut BotPool) {
+        let mut missing = Vec::new();
+
+        for board in boards.iter() {
+            let normalized = board.normalize().0;
+            if self.nodes.contains_key(&normalized) || missing.contains(&normalized) {
+                continue;
+            }
+
+            if Book::successors(&normalized).is_empty() {
+                let node = Node {
+                    score: 1000 * normalized.exact_score(),
+                    depth: 0,
+                    best_move: None,
+                    expanded: true,
+                    leaf_loss: None,
+                };
+                self.nodes.insert(normalized, node);
+            } else {
+                missing.push(normalized);
+            }
+        }
+
+        let depth = pool.search_depth();
+        let scores = pool.evaluate(&missing, &Limits::depth(depth), |_, _| {});
+
+        for (board, score) in missing.into_iter().zip(scores) {
+            let node = Node {
+                score,
+                depth,
+                best_move: None,
+                expanded: false,
+                leaf_loss: Some(0),
+            };
+            self.nodes.insert(board, node);
+        }
+    }
+
+    // Walks down from the root to the unexpanded position with the lowest summed score
+    // loss along the way, so the most plausible lines are grown first.
+    fn select_leaf(&self, root: &Board) -> Option<Board> {
+        let mut board = root.normalize().0;
+        let mut node = self.nodes.get(&board)?;
+        node.leaf_loss?;
+
+        while node.expanded {
+            let (child, child_node) = Book::successors(&board)
+                .into_iter()
+                .map(|(_, successor)| successor.normalize().0)
+                .filter_map(|successor| {
+                    let child = self.nodes.get(&successor)?;
+                    let loss = node.score + child.score + child.leaf_loss?;
+                    Some((loss, successor, child))
+                })
+                .min_by_key(|(loss, _, _)| *loss)
+                .map(|(_, successor, child)| (successor, child))?;
+
+            board = child;
+            node = child_node;
+        }
+
+        Some(board)
+    }
+
+    // Marks the position as expanded before its successors are added, so it is not
+    // selected again until they are evaluated.
+    fn mark_expanded(&mut self, board: &Board) {
+        if let Some(node) = self.nodes.get_mut(board) {
+            node.expanded = true;
+        }
+        self.add_parent_links(board);
+        self.backup(board);
+    }
+
+    fn add_parent_links(&mut self, board: &Board) {
+        for (_, successor) in Book::successors(board) {
+            let parents = self.parents.entry(successor.normalize().0).or_default();
+            if !parents.contains(board) {
+                parents.push(board.clone());
+            }
+        }
+    }
+
+    // Recomputes the position from its successors and passes changes on to the
+    // positions above it, leaving the rest of the book untouched.
+    fn backup(&mut self, board: &Board) {
+        self.update(board);
+
+        let mut stack: Vec<Board> = self.parents.get(board).cloned().unwrap_or_default();
+        while let Some(board) = stack.pop() {
+            if self.update(&board) {
+                stack.extend(self.parents.get(&board).cloned().unwrap_or_default());
+            }
+        }
+    }
+
+    // Sets the negamax score, best move and leaf loss of one position from its
+    // successors. Returns whether anything changed.
+    fn update(&mut self, board: &Board) -> bool {
+        let node = match self.nodes.get(board) {
+            Some(node) => node.clone(),
+            None => panic!("Position missing from book"),
+        };
+
+        let mut updated = node.clone();
+        let successors = Book::successors(board);
+
+        if !node.expanded {
+            updated.leaf_loss = Some(0);
+        } else if successors.is_empty() {
+            updated.leaf_loss = None;
+        } else {
+            let children: Option<Vec<(Option<usize>, Node)>> = successors
+                .iter()
+                .map(|(index, successor)| {
+                    let child = self.nodes.get(&successor.normalize().0)?;
+                    Some((*index, child.clone()))
+                })
+                .collect();
+
+            match children {
+                // successors are still being evaluated
+                None => updated.leaf_loss = None,
+                Some(children) => {
+                    let mut score = i32::MIN;
+                    let mut best_move = None;
+                    for (index, child) in children.iter() {
+                        if -child.score > score {
+                            score = -child.score;
+                            best_move = *index;
+                        }
+                    }
+
+                    updated.score = score;
+                    updated.best_move = best_move;
+                    updated.leaf_loss = children
+                        .iter()
+                        .filter_map(|(_, child)| Some(score + child.score + child.leaf_loss?))
+                        .min();
+                }
+            }
+        }
+
+        if updated == node {
+            return false;
+        }
+        self.nodes.insert(board.clone(), updated);
+        true
+    }
+
+    // Computes the values that are not saved, successors before the positions above them.
+    fn refresh(&mut self, board: &Board, done: &mut HashSet<Board>) {
+        if !done.insert(board.clone()) {
+            return;
+        }
+
+        if self.nodes[board].expanded {
+            for (_, successor) in Book::successors(board) {
+                let successor = successor.normalize().0;
+                if self.nodes.contains_key(&successor) {
+                    self.refresh(&successor, done);
+                }
+            }
+            self.add_parent_links(board);
+        }
+        self.update(board);
+    }
+
+    pub fn expand(&mut self, pool: &mut BotPool) -> bool {
+        self.expand_from(&Board::new(), pool)
+    }
+
+    // Evaluates the successors of the most promising leaves below root and updates
+    // the tree. Returns false when there is nothing left to expand.
+    pub fn expand_from(&mut self, root: &Board, pool: &mut BotPool) -> bool {
+        self.expand_roots(slice::from_ref(root), pool)[0]
+    }
+
+    // Expands below every root at once, so the pool evaluates the successors of all
+    // leaves in one batch. Leaves are taken from the roots in turn until the batch keeps
+    // every worker busy. Returns for every root whether anything was left to expand.
+    pub fn expand_roots(&mut self, roots: &[Board], pool: &mut BotPool) -> Vec<bool> {
+        self.add_all(roots, pool);
+
+        // selected leaves are marked right away, so later selections pick different ones
+        let batch_size = POSITIONS_PER_WORKER * pool.workers();
+        let mut leaves = Vec::new();
+        let mut successors = Vec::new();
+        let mut expanded = vec![false; roots.len()];
+        let mut open: Vec<usize> = (0..roots.len()).collect();
+
+        while !open.is_empty() && successors.len() < batch_size {
+            open.retain(|&i| match self.select_leaf(&roots[i]) {
+                Some(leaf) => {
+                    self.mark_expanded(&leaf);
+                    successors.extend(Book::successors(&leaf).into_iter().map(|(_, board)| board));
+                    leaves.push(leaf);
+                    expanded[i] = true;
+                    true
+                }
+                None => false,
+            });
+        }
+
+        self.add_all(&successors, pool);
+
+        for leaf in leaves.iter() {
+            self.backup(leaf);
+        }
+
+        expanded
+    }
+
+    // File layout: magic, version and record count, followed by records sorted on
+    // the normalized board. All integers are little endian.
+    // Record: me (u64), opp (u64), score (i32), depth (u8), best move (u8), flags (u8)
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // Write next to the book and rename, so stopping a run never leaves a broken file.
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+        let mut boards: Vec<&Board> = self.nodes.keys().collect();
+        boards.sort_by_key(|board| (board.me(), board.opp()));
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(boards.len() as u64).to_le_bytes())?;
+
+        for board in boards {
+            let node = &self.nodes[board];
+            writer.write_all(&board.me().to_le_bytes())?;
+            writer.write_all(&board.opp().to_le_bytes())?;
+            writer.write_all(&node.score.to_le_bytes())?;
+            writer.write_all(&[
+                node.depth.min(0xFF) as u8,
+                node.best_move.map_or(NO_MOVE, |index| index as u8),
+                node.expanded as u8,
+            ])?;
+        }
+
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Book> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let header_size = MAGIC.len() + 4 + 8;
+        if bytes.len() < header_size || &bytes[0..8] != MAGIC {
+            return Err(invalid("Not a book file"));
+        }
+        if u32::from_le_bytes(bytes[8..12].try_into().unwrap()) != VERSION {
+            return Err(invalid("Unsupported book version"));
+        }
+        let count = u64::from_le_bytes(bytes[12..20].try_into().unwrap()) as usize;
+        match count.checked_mul(RECORD_SIZE) {
+            Some(size) if size == bytes.len() - header_size => {}
+            _ => return Err(invalid("Truncated book")),
+        }
+
+        let mut book = Book::new();
+        for record in bytes[header_size..].chunks(RECORD_SIZE) {
+            let me = u64::from_le_bytes(record[0..8].try_into().unwrap());
+            let opp = u64::from_le_bytes(record[8..16].try_into().unwrap());
+            if me & opp != 0 {
+                return Err(invalid("Invalid board"));
+            }
+
+            let best_move = match record[21] {
+                NO_MOVE => None,
+                index if index < 64 => Some(index as usize),
+                _ => return Err(invalid("Invalid move")),
+            };
+
+            let node = Node {
+                score: i32::from_le_bytes(record[16..20].try_into().unwrap()),
+                depth: record[20] as u32,
+                best_move,
+                expanded: record[22] & 1 != 0,
+                leaf_loss: None,
+            };
+            book.nodes.insert(Board::from_bitboards(me, opp), node);
+        }
+
+        let boards: Vec<Board> = book.nodes.keys().cloned().collect();
+        let mut done = HashSet::new();
+        for board in boards.iter() {
+            book.refresh(board, &mut done);
+        }
+
+        Ok(book)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Book;
+    use crate::board::{Board, Transform};
+    use crate::bot::Bot;
+    use crate::pool::BotPool;
+    use crate::xot::parse_opening;
+    use std::env;
+    use std::io;
+
+    #[test]
+    fn test_book_expand() {
+        let mut pool = BotPool::new(2, || Bot::new(2, 1));
+        let mut book = Book::new();
+
+        assert!(book.expand(&mut pool));
+
+        // the 4 opening moves are symmetric
+        assert_eq!(book.len(), 2);
+
+        for _ in 0..20 {
+            assert!(book.expand(&mut pool));
+        }
+
+        // every expanded position holds the negamax score of its successors
+        for (board, node) in book.nodes.iter() {
+            if !node.expanded || Book::successors(board).is_empty() {
+                continue;
+            }
+            let best = Book::successors(board)
+                .iter()
+                .map(|(_, successor)| -book.score(successor).unwrap())
+                .max()
+                .unwrap();
+            assert_eq!(best, node.score);
+        }
+    }
+
+    #[test]
+    fn test_book_expand_batch() {
+        let mut pool = BotPool::new(4, || Bot::new(1, 1));
+        let mut book = Book::new();
+
+        // a single root expands several leaves per batch to keep the workers busy
+        for _ in 0..5 {
+            assert!(book.expand(&mut pool));
+        }
+        let expanded = book.nodes.values().filter(|node| node.expanded).count();
+        assert!(expanded > 5);
+
+        // nothing is left pending after a batch
+        for (board, node) in book.nodes.iter() {
+            if node.expanded {
+                for (_, successor) in Book::successors(board) {
+                    assert!(book.score(&successor).is_some());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_book_expand_from() {
+        let mut pool = BotPool::new(2, || Bot::new(2, 1));
+        let mut book = Book::new();
+        let root = parse_opening("f5d6c3d3c4f4c5b3").unwrap().board().clone();
+
+        for _ in 0..5 {
+            assert!(book.expand_from(&root, &mut pool));
+        }
+
+        assert!(book.score(&root).is_some());
+        assert!(book.score(&Board::new()).is_none());
+        for board in book.nodes.keys() {
+            assert!(board.count_discs() >= root.count_discs());
+        }
+    }
+
+    #[test]
+    fn test_book_lookup() {
+        let mut pool = BotPool::new(2, || Bot::new(2, 1));
+        let mut book = Book::new();
+        for _ in 0..20 {
+            book.expand(&mut pool);
+        }
+
+        assert!(book.lookup(&Board::new_random(30)).is_none());
+
+        for board in book.nodes.keys() {
+            for transform in Transform::all() {
+                let transformed = board.transform(transform);
+                let entry = book.lookup(&transformed).unwrap();
+                assert_eq!(Some(entry.score), book.score(board));
+
+                // the best move is legal and leads to a position with the opposite score
+                if let Some(index) = entry.best_move {
+                    let child = transformed.do_move(index);
+                    assert_eq!(Some(-entry.score), book.score(&child));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_book_save_load() {
+        let mut pool = BotPool::new(2, || Bot::new(2, 1));
+        let mut book = Book::new();
+        for _ in 0..10 {
+            book.expand(&mut pool);
+        }
+
+        let path = env::temp_dir().join("othello_bot_test_book_save_load.bin");
+        book.save(&path).unwrap();
+        let loaded = Book::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(book.nodes, loaded.nodes);
+        assert_eq!(book.score(&Board::new()), loaded.score(&Board::new()));
+    }
+
+    #[test]
+    fn test_book_load_invalid() {
+        let mut pool = BotPool::new(2, || Bot::new(2, 1));
+        let mut book = Book::new();
+        book.expand(&mut pool);
+
+        let path = env::temp_dir().join("othello_bot_test_book_load_invalid.bin");
+        book.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+
+        // a record count that overflows the file size is rejected, not multiplied
+        let mut corrupt = bytes.clone();
+        corrupt[12..20].copy_from_slice(&(1u64 << 62).to_le_bytes());
+        let invalid = [corrupt, bytes[..bytes.len() - 1].to_vec(), b"NOTABOOK".to_vec()];
+
+        for bytes in invalid.iter() {
+            std::fs::write(&path, bytes).unwrap();
+            let error = Book::load(&path).err().unwrap();
+            assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
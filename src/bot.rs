@@ -1,64 +1,619 @@
 use crate::board::Board;
-use std::time::Instant;
+use crate::book::Book;
+use crate::endgame::{Solver, WldResult};
+use crate::evaluation::{Evaluator, SimpleEvaluator};
+use crate::limits::{Budget, Limits, StopHandle};
+use crate::moves::Move;
+use crate::transposition::TranspositionTable;
+use std::cmp::Reverse;
+use std::mem;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub best_move: Move,
+    // position after the best move, seen from the opponent
+    pub board: Board,
+    // expected line of play starting with the best move
+    pub pv: Vec<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub stats: SearchStats,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    pub nodes: u64,
+    pub leaf_evaluations: u64,
+    pub tt_hits: u64,
+    // beta cutoffs by the index of the move that caused them, later moves share the last entry
+    pub cutoffs: [u64; 8],
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    // Effective branching factor, the number of nodes grows by about this factor per ply.
+    pub fn branching_factor(&self, depth: u32) -> f64 {
+        if depth == 0 {
+            return 0.0;
+        }
+        (self.nodes as f64).powf(1.0 / depth as f64)
+    }
+
+    // Share of the cutoffs caused by the first move searched, which is high when
+    // moves are ordered well.
+    pub fn first_move_cutoffs(&self) -> f64 {
+        let total: u64 = self.cutoffs.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+        self.cutoffs[0] as f64 / total as f64
+    }
+
+    pub fn nodes_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.nodes as f64 / seconds
+    }
+
+    // Adds the counts of another search, for example of a helper thread. Keeps the elapsed time.
+    pub fn add(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.leaf_evaluations += other.leaf_evaluations;
+        self.tt_hits += other.tt_hits;
+        for (count, other) in self.cutoffs.iter_mut().zip(other.cutoffs.iter()) {
+            *count += other;
+        }
+    }
+
+    // Part of these stats collected after the earlier snapshot was taken.
+    fn since(&self, earlier: &SearchStats) -> SearchStats {
+        let mut cutoffs = self.cutoffs;
+        for (count, before) in cutoffs.iter_mut().zip(earlier.cutoffs.iter()) {
+            *count -= before;
+        }
+
+        SearchStats {
+            nodes: self.nodes - earlier.nodes,
+            leaf_evaluations: self.leaf_evaluations - earlier.leaf_evaluations,
+            tt_hits: self.tt_hits - earlier.tt_hits,
+            cutoffs,
+            elapsed: self.elapsed - earlier.elapsed,
+        }
+    }
+}
+
+// Nodes with at least this depth left order their moves by a shallow search.
+const SHALLOW_ORDERING_DEPTH: u32 = 5;
+
+// Called with the result of every completed iteration of a search.
+pub type Observer = Box<dyn FnMut(&SearchResult) + Send>;
 
 pub struct Bot {
     search_depth: u32,
-    nodes: u64,
+    endgame_empties: u32,
+    stats: SearchStats,
+    start: Instant,
+    observer: Option<Observer>,
+    table: Arc<TranspositionTable>,
+    book: Option<Book>,
+    evaluator: Arc<dyn Evaluator>,
+    budget: Budget,
+    aborted: bool,
+    stop: StopHandle,
+    threads: usize,
+    // two moves per number of discs that recently caused a cutoff
+    killers: [[Option<usize>; 2]; 65],
+    // cutoffs by square, weighted by the square of the remaining depth
+    history: [u64; 64],
 }
 
 impl Bot {
-    pub fn new(search_depth: u32) -> Self {
+    pub fn new(search_depth: u32, table_megabytes: usize) -> Self {
         Bot {
             search_depth: search_depth,
-            nodes: 0,
+            endgame_empties: 0,
+            stats: SearchStats::default(),
+            start: Instant::now(),
+            observer: None,
+            table: Arc::new(TranspositionTable::new(table_megabytes)),
+            book: None,
+            evaluator: Arc::new(SimpleEvaluator),
+            budget: Budget::unlimited(),
+            aborted: false,
+            stop: StopHandle::new(),
+            threads: 1,
+            killers: [[None; 2]; 65],
+            history: [0; 64],
         }
     }
 
-    pub fn do_move(&mut self, board: &Board) -> Board {
-        let start = Instant::now();
+    // Positions with fewer than this many empty squares are solved exactly, 0 disables solving.
+    pub fn set_endgame_empties(&mut self, empties: u32) {
+        self.endgame_empties = empties;
+    }
+
+    pub fn set_book(&mut self, book: Book) {
+        self.book = Some(book);
+    }
+
+    // Replaces the default corner and mobility evaluation.
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+
+    // Helper threads search the same position and share the transposition table, which
+    // makes the search on the calling thread faster. With 1 thread searches are deterministic.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn set_observer(&mut self, observer: Observer) {
+        self.observer = Some(observer);
+    }
+
+    // Stopping ends the running search with the best move found so far. The flag is
+    // cleared when a search starts and ends, a stop while no search runs is ignored.
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    pub fn do_move(&mut self, board: &Board) -> SearchResult {
+        let limits = Limits::depth(self.search_depth);
+        self.search(board, &limits)
+    }
+
+    // Searches one ply deeper every iteration until the limits are reached. When
+    // the search is cut off, the best move of the last completed iteration is used.
+    // Passes when the player to move has no moves, panics when the game is over.
+    // Node limits apply to the calling thread, the stats count the nodes of all threads.
+    pub fn search(&mut self, board: &Board, limits: &Limits) -> SearchResult {
+        self.stop.clear();
+        let (mut result, helpers) =
+            self.with_helpers(board, |bot| bot.search_with_limits(board, limits));
+        result.stats.add(&helpers);
+        self.stop.clear();
+        result
+    }
+
+    // Runs the search on this thread while the helper threads search the same position
+    // until it is done. Returns the result with the summed stats of the helpers.
+    fn with_helpers<T, F>(&mut self, board: &Board, search: F) -> (T, SearchStats)
+    where
+        F: FnOnce(&mut Bot) -> T,
+    {
+        let stop = StopHandle::new();
+
+        let handles: Vec<_> = (1..self.threads)
+            .map(|id| {
+                let mut helper = self.helper(&stop);
+                let board = board.clone();
+                thread::spawn(move || {
+                    helper.help(&board, id);
+                    helper.stats
+                })
+            })
+            .collect();
+
+        let result = search(self);
+        stop.stop();
+
+        let mut stats = SearchStats::default();
+        for handle in handles {
+            stats.add(&handle.join().unwrap());
+        }
+        (result, stats)
+    }
+
+    fn helper(&self, stop: &StopHandle) -> Bot {
+        Bot {
+            search_depth: self.search_depth,
+            endgame_empties: self.endgame_empties,
+            stats: SearchStats::default(),
+            start: Instant::now(),
+            observer: None,
+            table: self.table.clone(),
+            book: None,
+            evaluator: self.evaluator.clone(),
+            budget: Budget::unlimited(),
+            aborted: false,
+            stop: stop.clone(),
+            threads: 1,
+            killers: [[None; 2]; 65],
+            history: [0; 64],
+        }
+    }
+
+    // Iterative deepening without results until stopped, which only fills the shared
+    // transposition table. Helpers start at different depths and with different moves,
+    // so they do not all search the same positions at the same time.
+    fn help(&mut self, board: &Board, id: usize) {
+        self.start_search(board, &Limits::default());
+
+        let mut children = board.children_with_moves();
+        if children.is_empty() {
+            return;
+        }
+        let count = children.len();
+        children.rotate_left(id % count);
+
+        let mut depth = 1 + (id % 2) as u32;
+        loop {
+            let mut alpha = -64000;
+            for (_, child) in children.iter() {
+                let heuristic = -self.pvs(child, -64000, -alpha, depth - 1);
+                if self.aborted {
+                    return;
+                }
+                alpha = alpha.max(heuristic);
+            }
 
-        let children = board.children();
+            if self.iteration_is_exact(board, depth) {
+                return;
+            }
+            depth += 1;
+        }
+    }
+
+    // Searches the position after the expected reply of the opponent until stopped, so the
+    // transposition table is filled if the opponent plays it. Returns the expected position.
+    pub fn ponder(&mut self, board: &Board) -> Option<Board> {
+        self.stop.clear();
+        let expected = self.expected_reply(board);
+
+        if let Some(expected) = &expected {
+            if expected.has_moves() {
+                self.search_with_limits(expected, &Limits::default());
+            }
+        }
+
+        self.stop.clear();
+        expected
+    }
+
+    fn expected_reply(&self, board: &Board) -> Option<Board> {
+        if let Some(index) = self.table.lookup(board).and_then(|entry| entry.best_move) {
+            return Some(board.do_move(index));
+        }
+
+        board
+            .children()
+            .into_iter()
+            .min_by_key(|child| self.heuristic(child))
+    }
+
+    // Scores every legal move with a full window, ordered by square unless sorted is set,
+    // in which case the best move comes first. Uses the results of the last iteration
+    // that scored all moves when the limits are reached.
+    pub fn analyze(&mut self, board: &Board, limits: &Limits, sorted: bool) -> Vec<SearchResult> {
+        self.stop.clear();
+        self.start_search(board, limits);
+
+        let children = board.children_with_moves();
+        let mut results: Vec<SearchResult> = Vec::new();
+        let max_depth = limits.depth.unwrap_or(board.count_empties() + 1);
+
+        for depth in 1..=max_depth {
+            let mut iteration = Vec::new();
+            let limited = self.unlimited_first_iteration(depth);
+
+            for (index, child) in children.iter() {
+                let before = self.current_stats();
+                let score = -self.pvs(child, -64000, 64000, depth - 1);
+                if self.aborted {
+                    break;
+                }
+
+                iteration.push(SearchResult {
+                    best_move: Move::Square(*index),
+                    board: child.clone(),
+                    pv: self.principal_variation(board, *index, score, depth),
+                    score,
+                    depth,
+                    stats: self.current_stats().since(&before),
+                });
+            }
+
+            if let Some(budget) = limited {
+                self.budget = budget;
+            }
+            if self.aborted {
+                break;
+            }
+            results = iteration;
+
+            if self.iteration_is_exact(board, depth) || !self.budget.allows_iteration() {
+                break;
+            }
+        }
+
+        self.stop.clear();
+
+        if sorted {
+            results.sort_by_key(|result| -result.score);
+        }
+        results
+    }
+
+    // The first iteration ignores the limits and stop requests, so every search returns a
+    // searched move. Returns the budget to restore once the iteration is done.
+    fn unlimited_first_iteration(&mut self, depth: u32) -> Option<Budget> {
+        if depth > 1 {
+            return None;
+        }
+        Some(mem::replace(&mut self.budget, Budget::unlimited()))
+    }
+
+    fn start_search(&mut self, board: &Board, limits: &Limits) {
+        self.stats = SearchStats::default();
+        self.start = Instant::now();
+
+        // older searches say less about this position
+        self.killers = [[None; 2]; 65];
+        for count in self.history.iter_mut() {
+            *count /= 2;
+        }
+        self.budget = Budget::new(limits, board.count_empties(), &self.stop);
+        self.aborted = false;
+    }
+
+    fn current_stats(&self) -> SearchStats {
+        let mut stats = self.stats.clone();
+        stats.elapsed = self.start.elapsed();
+        stats
+    }
+
+    fn notify(&mut self, result: &SearchResult) {
+        if let Some(observer) = &mut self.observer {
+            observer(result);
+        }
+    }
+
+    fn search_with_limits(&mut self, board: &Board, limits: &Limits) -> SearchResult {
+        self.start_search(board, limits);
+
+        let book_entry = self.book.as_ref().and_then(|book| book.lookup(board));
+        if let Some(entry) = book_entry {
+            if let Some(index) = entry.best_move {
+                let result = SearchResult {
+                    best_move: Move::Square(index),
+                    board: board.do_move(index),
+                    pv: vec![Move::Square(index)],
+                    score: entry.score,
+                    depth: entry.depth,
+                    stats: self.current_stats(),
+                };
+                self.notify(&result);
+                return result;
+            }
+        }
+
+        let mut children = board.children_with_moves();
 
         if children.len() == 0 {
-            panic!("No children")
+            let mut passed = board.clone();
+            passed.switch_turn();
+            if !passed.has_moves() {
+                panic!("No children")
+            }
+
+            let mut result = self.search_with_limits(&passed, limits);
+            result.best_move = Move::Pass;
+            result.board = passed;
+            result.pv.insert(0, Move::Pass);
+            result.score = -result.score;
+            return result;
         }
 
-        let mut best_child = children[0].clone();
-        let mut alpha = -64000;
-        let beta = 64000;
+        let mut result = SearchResult {
+            best_move: Move::Square(children[0].0),
+            board: children[0].1.clone(),
+            pv: vec![Move::Square(children[0].0)],
+            score: 0,
+            depth: 0,
+            stats: SearchStats::default(),
+        };
 
-        for (i, child) in children.iter().enumerate() {
-            let heuristic = -self.pvs(child, -beta, -alpha, self.search_depth);
-            if heuristic > alpha {
-                alpha = heuristic;
-                best_child = child.clone();
+        let max_depth = limits.depth.unwrap_or(board.count_empties() + 1);
+
+        for depth in 1..=max_depth {
+            let mut best = 0;
+            let mut alpha = -64000;
+            let beta = 64000;
+            let limited = self.unlimited_first_iteration(depth);
+
+            for (i, (_, child)) in children.iter().enumerate() {
+                let heuristic = -self.pvs(child, -beta, -alpha, depth - 1);
+                if self.aborted {
+                    break;
+                }
+                if heuristic > alpha {
+                    alpha = heuristic;
+                    best = i;
+                }
             }
 
-            let duration = start.elapsed().as_secs_f32();
-            println!(
-                "Child {:2}/{:2}: {:6} | {:9} nodes in {:4.2} sec = {:9} nodes/sec",
-                i + 1,
-                children.len(),
-                heuristic,
-                self.nodes,
-                duration,
-                ((self.nodes as f32) / duration) as i32
-            );
+            if let Some(budget) = limited {
+                self.budget = budget;
+            }
+
+            if self.aborted {
+                break;
+            }
+
+            // the best move is searched first in the next iteration
+            children[..=best].rotate_right(1);
+
+            result.best_move = Move::Square(children[0].0);
+            result.board = children[0].1.clone();
+            result.pv = self.principal_variation(board, children[0].0, alpha, depth);
+            result.score = alpha;
+            result.depth = depth;
+            result.stats = self.current_stats();
+            self.notify(&result);
+
+            if self.iteration_is_exact(board, depth) || !self.budget.allows_iteration() {
+                break;
+            }
         }
 
-        self.nodes = 0;
+        result.stats = self.current_stats();
+        result
+    }
+
+    // Deeper searches cannot change the result once every line reaches the end of the game.
+    // Full boards are only recognized with depth left, hence the extra ply. Children with
+    // fewer than endgame_empties empty squares are solved exactly at any depth.
+    fn iteration_is_exact(&self, board: &Board, depth: u32) -> bool {
+        depth > board.count_empties() || board.count_empties() <= self.endgame_empties
+    }
+
+    // Follows the best moves in the transposition table up to the search depth. The table
+    // holds no complete lines below endgame_empties, so the line is completed to the end of
+    // the game by solving the first of them, using the score of the line for the player to
+    // move on board.
+    fn principal_variation(
+        &self,
+        board: &Board,
+        first: usize,
+        score: i32,
+        depth: u32,
+    ) -> Vec<Move> {
+        let mut pv = vec![Move::Square(first)];
+        let mut current = board.do_move(first);
+        let mut score = -score;
+        let mut played = 1;
 
-        best_child
+        while played < depth || current.count_empties() < self.endgame_empties {
+            if current.count_empties() < self.endgame_empties {
+                pv.extend(self.solved_line(&current, score));
+                break;
+            }
+
+            if !current.has_moves() {
+                current.switch_turn();
+                if !current.has_moves() {
+                    break;
+                }
+                pv.push(Move::Pass);
+                score = -score;
+                continue;
+            }
+
+            let index = match self.table.lookup(&current).and_then(|entry| entry.best_move) {
+                Some(index) => index,
+                None => break,
+            };
+            pv.push(Move::Square(index));
+            current = current.do_move(index);
+            score = -score;
+            played += 1;
+        }
+
+        pv
+    }
+
+    // Best line of a position the search solved with the given score. The window only just
+    // contains the score, so this is fast, and the limits of the search still apply.
+    // Returns no moves when the solve is cut off or finds a different score.
+    fn solved_line(&self, board: &Board, score: i32) -> Vec<Move> {
+        let mut solver = Solver::with_budget(self.budget.clone(), self.stats.nodes);
+        let (alpha, beta) = (score.div_euclid(1000) - 1, -(-score).div_euclid(1000) + 1);
+        let solved = solver.solve(board, alpha, beta);
+
+        if solver.aborted() || solved <= alpha || solved >= beta {
+            return vec![];
+        }
+        solver
+            .line()
+            .iter()
+            .map(|played| match played {
+                Some(index) => Move::Square(*index),
+                None => Move::Pass,
+            })
+            .collect()
+    }
+
+    pub fn search_depth(&self) -> u32 {
+        self.search_depth
+    }
+
+    pub fn evaluate(&mut self, board: &Board) -> i32 {
+        let (score, helpers) = self.with_helpers(board, |bot| {
+            bot.budget = Budget::unlimited();
+            bot.aborted = false;
+            bot.pvs(board, -64000, 64000, bot.search_depth)
+        });
+        self.stats.add(&helpers);
+        score
+    }
+
+    fn check_budget(&mut self) -> bool {
+        if self.budget.poll(self.stats.nodes) {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    // Exact score scaled like the 1000 * exact_score of terminal positions. Solved bounds
+    // go into the table, so positions that searches reach again are not solved again.
+    fn solve(&mut self, board: &Board, alpha: i32, beta: i32) -> i32 {
+        let mut solver = Solver::with_budget(self.budget.clone(), self.stats.nodes);
+        solver.set_table(self.table.clone());
+        let score = solver.solve(board, alpha.div_euclid(1000), -(-beta).div_euclid(1000));
+        self.stats.nodes = solver.nodes();
+        self.aborted |= solver.aborted();
+
+        (1000 * score).max(alpha).min(beta)
+    }
+
+    // Only proves whether the player to move wins, draws or loses, which is much cheaper than
+    // solving the exact score. Always searches to the end, regardless of the number of empties.
+    pub fn solve_wld(&mut self, board: &Board) -> WldResult {
+        let mut solver = Solver::new();
+        let result = solver.solve_wld(board);
+        self.stats.nodes += solver.nodes();
+        result
     }
 
     fn pvs(&mut self, board: &Board, mut alpha: i32, beta: i32, depth: u32) -> i32 {
-        self.nodes += 1;
+        if board.count_empties() < self.endgame_empties {
+            return self.solve(board, alpha, beta);
+        }
+
+        self.stats.nodes += 1;
+
+        if self.check_budget() {
+            return 0;
+        }
 
         if depth == 0 {
+            self.stats.leaf_evaluations += 1;
             return self.heuristic(board);
         }
 
-        let mut children = board.children();
+        let mut hash_move = None;
+        if let Some(entry) = self.table.lookup(board) {
+            self.stats.tt_hits += 1;
+            if entry.depth >= depth {
+                if entry.lower >= beta {
+                    return beta;
+                }
+                if entry.upper <= alpha {
+                    return alpha;
+                }
+                if entry.lower == entry.upper {
+                    return entry.lower;
+                }
+            }
+            hash_move = entry.best_move;
+        }
+
+        let mut children = board.children_with_moves();
 
         if children.len() == 0 {
             let mut passed = board.clone();
@@ -69,9 +624,12 @@ impl Bot {
             return -self.pvs(&passed, -beta, -alpha, depth);
         }
 
-        children.sort_by(|lhs, rhs| self.heuristic(lhs).cmp(&self.heuristic(rhs)));
+        self.order_moves(board, &mut children, hash_move, depth);
+
+        let original_alpha = alpha;
+        let mut best_move = hash_move;
 
-        for (i,child) in children.iter().enumerate() {
+        for (i, (index, child)) in children.iter().enumerate() {
             let mut heuristic;
             if i == 0 {
                 heuristic = -self.pvs(&child, -beta, -alpha, depth - 1);
@@ -82,26 +640,64 @@ impl Bot {
                 }
             }
 
+            if self.aborted {
+                return 0;
+            }
 
             if heuristic >= beta {
+                self.record_cutoff(board, *index, i, depth);
+                self.table.store(board, depth, beta, 64000, Some(*index));
                 return beta;
             }
             if heuristic > alpha {
                 alpha = heuristic;
+                best_move = Some(*index);
             }
         }
 
+        if alpha > original_alpha {
+            self.table.store(board, depth, alpha, alpha, best_move);
+        } else {
+            self.table.store(board, depth, -64000, alpha, best_move);
+        }
+
         alpha
     }
 
     fn null_window(&mut self, board: &Board, alpha: i32, depth: u32) -> i32 {
-        self.nodes += 1;
+        if board.count_empties() < self.endgame_empties {
+            if self.solve(board, alpha, alpha + 1) > alpha {
+                return alpha + 1;
+            }
+            return alpha;
+        }
+
+        self.stats.nodes += 1;
+
+        if self.check_budget() {
+            return 0;
+        }
 
         if depth == 0 {
+            self.stats.leaf_evaluations += 1;
             return self.heuristic(board);
         }
 
-        let children = board.children();
+        let mut hash_move = None;
+        if let Some(entry) = self.table.lookup(board) {
+            self.stats.tt_hits += 1;
+            if entry.depth >= depth {
+                if entry.lower > alpha {
+                    return alpha + 1;
+                }
+                if entry.upper <= alpha {
+                    return alpha;
+                }
+            }
+            hash_move = entry.best_move;
+        }
+
+        let mut children = board.children_with_moves();
 
         if children.len() == 0 {
             let mut passed = board.clone();
@@ -112,28 +708,96 @@ impl Bot {
             return -self.null_window(&passed, -(alpha+1), depth);
         }
 
-        for child in children.iter() {
+        self.order_moves(board, &mut children, hash_move, depth);
+
+        for (i, (index, child)) in children.iter().enumerate() {
             let heuristic = -self.null_window(&child, -(alpha+1), depth - 1);
+            if self.aborted {
+                return 0;
+            }
             if heuristic > alpha {
+                self.record_cutoff(board, *index, i, depth);
+                self.table.store(board, depth, alpha + 1, 64000, Some(*index));
                 return alpha + 1;
             }
         }
 
+        self.table.store(board, depth, -64000, alpha, hash_move);
         alpha
     }
 
+    // Hash move first, then the killer moves for this number of discs. The other moves are
+    // ordered by a shallow search at high depths, otherwise by history and evaluation.
+    fn order_moves(
+        &mut self,
+        board: &Board,
+        children: &mut Vec<(usize, Board)>,
+        hash_move: Option<usize>,
+        depth: u32,
+    ) {
+        let killers = self.killers[board.count_discs() as usize];
+        let mut keys = Vec::with_capacity(children.len());
+
+        for (index, child) in children.iter() {
+            let key = if Some(*index) == hash_move {
+                (2, 0, 0)
+            } else if killers.contains(&Some(*index)) {
+                (1, 0, 0)
+            } else if depth >= SHALLOW_ORDERING_DEPTH {
+                (0, -self.pvs(child, -64000, 64000, depth / 4) as i64, 0)
+            } else {
+                (0, self.history[*index] as i64, -self.heuristic(child))
+            };
+            keys.push(key);
+        }
+
+        let mut keyed: Vec<_> = keys.into_iter().zip(children.drain(..)).collect();
+        keyed.sort_by_key(|(key, _)| Reverse(*key));
+        children.extend(keyed.into_iter().map(|(_, child)| child));
+    }
+
+    fn record_cutoff(&mut self, board: &Board, index: usize, i: usize, depth: u32) {
+        let last = self.stats.cutoffs.len() - 1;
+        self.stats.cutoffs[i.min(last)] += 1;
+
+        let killers = &mut self.killers[board.count_discs() as usize];
+        if killers[0] != Some(index) {
+            killers[1] = killers[0];
+            killers[0] = Some(index);
+        }
+
+        self.history[index] += (depth * depth) as u64;
+    }
+
     fn heuristic(&self, board: &Board) -> i32 {
-        5 * board.corner_difference() + board.potential_moves_difference()
+        self.evaluator.evaluate(board)
     }
 }
 
+pub fn format_pv(pv: &[Move]) -> String {
+    let moves: Vec<String> = pv.iter().map(|played| played.to_string()).collect();
+    moves.join(" ")
+}
+
+
 
 
 #[cfg(test)]
 mod tests {
     use crate::board::tests::generate_test_boards;
+    use crate::book::Book;
     use crate::bot::Board;
-    use super::Bot;
+    use crate::endgame::Solver;
+    use crate::evaluation::Evaluator;
+    use crate::limits::{Budget, Limits, StopHandle};
+    use crate::moves::{replay, Move};
+    use crate::pool::BotPool;
+    use crate::xot::parse_opening;
+    use crate::position::Position;
+    use super::{Bot, SearchResult};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     impl Bot {
         fn minimax(&mut self, board: &Board, depth: u32, is_max: bool) -> i32 {
@@ -176,7 +840,7 @@ mod tests {
         }
 
         fn alpha_beta(&mut self, board: &Board, mut alpha: i32, beta: i32, depth: u32) -> i32 {
-            self.nodes += 1;
+            self.stats.nodes += 1;
 
             if depth == 0 {
                 return self.heuristic(board);
@@ -213,7 +877,7 @@ mod tests {
     fn test_alphabeta() {
         let boards = generate_test_boards();
         let depth = 3;
-        let mut bot = Bot::new(depth);
+        let mut bot = Bot::new(depth, 0);
 
         for board in boards.iter() {
             let minimax = bot.minimax(board, depth, true);
@@ -231,7 +895,7 @@ mod tests {
     fn test_pvs() {
         let boards = generate_test_boards();
         let depth = 4;
-        let mut bot = Bot::new(depth);
+        let mut bot = Bot::new(depth, 0);
 
         for board in boards.iter() {
             let alpha_beta = bot.alpha_beta(board, -64000, 64000, depth);
@@ -245,4 +909,427 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pvs_transposition_table() {
+        let boards = generate_test_boards();
+        let depth = 4;
+
+        for board in boards.iter() {
+            let mut bot = Bot::new(depth, 1);
+            let alpha_beta = bot.alpha_beta(board, -64000, 64000, depth);
+            let pvs = bot.pvs(board, -64000, 64000, depth);
+            assert_eq!(alpha_beta, pvs);
+
+            // second search is answered from the table, possibly after a pass
+            let nodes = bot.stats.nodes;
+            assert_eq!(pvs, bot.pvs(board, -64000, 64000, depth));
+            assert!(bot.stats.nodes - nodes <= 2);
+        }
+    }
+
+    #[test]
+    fn test_do_move_book() {
+        let mut book = Book::new();
+        let mut pool = BotPool::new(1, || Bot::new(2, 1));
+        for _ in 0..5 {
+            book.expand(&mut pool);
+        }
+
+        let board = Board::new();
+        let book_move = book.lookup(&board).unwrap().best_move.unwrap();
+
+        let mut bot = Bot::new(6, 1);
+        bot.set_book(book);
+
+        let result = bot.do_move(&board);
+        assert_eq!(Move::Square(book_move), result.best_move);
+        assert_eq!(board.do_move(book_move), result.board);
+        assert_eq!(bot.stats.nodes, 0);
+    }
+
+    #[test]
+    fn test_pvs_endgame() {
+        let boards = generate_test_boards();
+        let depth = 3;
+
+        for board in boards.iter() {
+            if board.count_empties() > 8 {
+                continue;
+            }
+
+            let mut bot = Bot::new(depth, 1);
+            let exact = bot.alpha_beta(board, -64000, 64000, 64);
+
+            bot.set_endgame_empties(9);
+            assert_eq!(exact, bot.pvs(board, -64000, 64000, depth));
+            assert_eq!(exact, bot.evaluate(board));
+        }
+    }
+
+    #[test]
+    fn test_search_endgame_single_iteration() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter() {
+            if board.count_empties() > 8 || !board.has_moves() {
+                continue;
+            }
+
+            let mut bot = Bot::new(6, 1);
+            let exact = bot.alpha_beta(board, -64000, 64000, 64);
+
+            // every child is solved exactly, so deeper iterations would only repeat the solve
+            bot.set_endgame_empties(9);
+            let result = bot.search(board, &Limits::default());
+            assert_eq!(1, result.depth);
+            assert_eq!(exact, result.score);
+
+            let results = bot.analyze(board, &Limits::default(), true);
+            assert!(results.iter().all(|result| result.depth == 1));
+            assert_eq!(exact, results[0].score);
+        }
+    }
+
+    #[test]
+    fn test_search_endgame_pv() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter() {
+            if board.count_empties() < 10 || board.count_empties() > 12 || !board.has_moves() {
+                continue;
+            }
+
+            let mut bot = Bot::new(4, 1);
+            bot.set_endgame_empties(9);
+            let result = bot.search(board, &Limits::depth(4));
+
+            // once the principal variation reaches the solved part it continues to the end
+            let mut position = Position::from_board(board.clone(), false);
+            let mut solved = false;
+            for played in result.pv.iter() {
+                position = position.play(*played).unwrap();
+                solved |= position.board().count_empties() < 9;
+            }
+            assert!(!solved || position.is_game_over());
+        }
+    }
+
+    #[test]
+    fn test_do_move_limits() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter().filter(|board| board.count_discs() == 20) {
+            if !board.has_moves() {
+                continue;
+            }
+            let children = board.children();
+            let mut bot = Bot::new(4, 1);
+
+            let limits = Limits {
+                nodes: Some(1),
+                ..Limits::default()
+            };
+            assert!(children.contains(&bot.search(board, &limits).board));
+
+            let start = Instant::now();
+            let limits = Limits::move_time(Duration::from_millis(20));
+            assert!(children.contains(&bot.search(board, &limits).board));
+            assert!(start.elapsed() < Duration::from_millis(500));
+
+            // aborted searches leave no wrong results in the transposition table
+            let mut limits = Limits::depth(5);
+            limits.nodes = Some(3000);
+            let mut fresh = Bot::new(5, 1);
+            let mut aborted = Bot::new(5, 1);
+            aborted.search(board, &limits);
+            assert_eq!(fresh.evaluate(board), aborted.evaluate(board));
+        }
+    }
+
+    #[test]
+    fn test_stop_handle() {
+        let board = Board::new();
+        let mut bot = Bot::new(60, 1);
+        let stop = bot.stop_handle();
+
+        let start = Instant::now();
+        let search = thread::spawn(move || bot.do_move(&board));
+        thread::sleep(Duration::from_millis(50));
+        stop.stop();
+
+        let result = search.join().unwrap();
+        assert!(Board::new().children().contains(&result.board));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!stop.is_stopped());
+    }
+
+    #[test]
+    fn test_stop_before_search() {
+        let board = Board::new();
+        let mut bot = Bot::new(4, 1);
+        let stop = bot.stop_handle();
+
+        // a stop that arrives after a search ended does not end the next one
+        stop.stop();
+        let result = bot.do_move(&board);
+        assert_eq!(4, result.depth);
+        assert!(!stop.is_stopped());
+
+        stop.stop();
+        assert!(bot.analyze(&board, &Limits::depth(4), true).iter().all(|r| r.depth == 4));
+    }
+
+    #[test]
+    fn test_solved_line() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter().filter(|board| board.count_empties() == 10) {
+            let score = Solver::new().solve(board, -65, 65);
+            let mut bot = Bot::new(4, 1);
+
+            let mut position = Position::from_board(board.clone(), false);
+            for played in bot.solved_line(board, 1000 * score) {
+                position = position.play(played).unwrap();
+            }
+            assert!(position.is_game_over());
+            assert_eq!(score, position.black_score());
+
+            // a line is only given for the score the search found, within its limits
+            assert!(bot.solved_line(board, 1000 * (score + 2)).is_empty());
+            let stop = StopHandle::new();
+            stop.stop();
+            bot.budget = Budget::new(&Limits::default(), 10, &stop);
+            assert!(bot.solved_line(board, 1000 * score).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_search_first_iteration() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter().filter(|board| board.has_moves()) {
+            let best = board
+                .children()
+                .iter()
+                .map(|child| -Bot::new(0, 1).evaluate(child))
+                .max()
+                .unwrap();
+
+            // even the smallest limits complete the first iteration
+            let limits = Limits {
+                nodes: Some(1),
+                ..Limits::default()
+            };
+            let result = Bot::new(4, 1).search(board, &limits);
+            assert_eq!(1, result.depth);
+            assert_eq!(best, result.score);
+
+            let limits = Limits {
+                time_left: Some(Duration::from_nanos(1)),
+                ..Limits::default()
+            };
+            let results = Bot::new(4, 1).analyze(board, &limits, true);
+            assert_eq!(board.children().len(), results.len());
+            assert_eq!(best, results[0].score);
+        }
+    }
+
+    #[test]
+    fn test_ponder() {
+        let mut bot = Bot::new(4, 1);
+        let board = bot.do_move(&Board::new()).board;
+        let stop = bot.stop_handle();
+
+        let ponder = thread::spawn(move || {
+            let expected = bot.ponder(&board);
+            (bot, board, expected)
+        });
+        thread::sleep(Duration::from_millis(50));
+        stop.stop();
+
+        let (mut bot, board, expected) = ponder.join().unwrap();
+        let expected = expected.unwrap();
+        assert!(board.children().contains(&expected));
+
+        // the search after the expected reply is found in the transposition table
+        let children = expected.children();
+        assert!(children.iter().any(|child| bot.table.lookup(child).is_some()));
+        assert!(expected.children().contains(&bot.do_move(&expected).board));
+    }
+
+    #[test]
+    fn test_search_result() {
+        let boards = generate_test_boards();
+        let depth = 3;
+
+        for board in boards.iter() {
+            if !board.has_moves() {
+                continue;
+            }
+
+            let mut bot = Bot::new(depth, 1);
+            let result = bot.search(board, &Limits::depth(depth));
+            assert_eq!(Bot::new(depth, 1).evaluate(board), result.score);
+            assert!(result.depth <= depth);
+            assert!(result.stats.nodes > 0);
+
+            // the principal variation is a legal line
+            let played = result.pv.iter().filter(|m| **m != Move::Pass).count();
+            assert!(played >= 1 && played <= depth as usize);
+            assert!(replay(&Position::from_board(board.clone(), false), &result.pv).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let boards = generate_test_boards();
+        let depth = 3;
+
+        for board in boards.iter() {
+            let mut bot = Bot::new(depth, 1);
+            let results = bot.analyze(board, &Limits::depth(depth), false);
+            assert_eq!(board.children().len(), results.len());
+
+            for (result, (index, child)) in results.iter().zip(board.children_with_moves()) {
+                assert_eq!(Move::Square(index), result.pv[0]);
+                assert_eq!(-Bot::new(depth - 1, 1).evaluate(&child), result.score);
+            }
+
+            let sorted = bot.analyze(board, &Limits::depth(depth), true);
+            for pair in sorted.windows(2) {
+                assert!(pair[0].score >= pair[1].score);
+            }
+            if let Some(best) = sorted.first() {
+                assert_eq!(Bot::new(depth, 1).evaluate(board), best.score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_move() {
+        let boards = generate_test_boards();
+
+        for board in boards.iter() {
+            let mut passed = board.clone();
+            passed.switch_turn();
+            if !board.has_moves() && !passed.has_moves() {
+                continue;
+            }
+
+            let mut bot = Bot::new(2, 1);
+            let result = bot.do_move(board);
+            assert_eq!(result.best_move, result.pv[0]);
+
+            match result.best_move {
+                Move::Square(index) => assert_eq!(board.do_move(index), result.board),
+                Move::Pass => {
+                    assert!(!board.has_moves());
+                    assert_eq!(passed, result.board);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_stats() {
+        let board = Board::new();
+        let mut bot = Bot::new(5, 1);
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let observed = reported.clone();
+        bot.set_observer(Box::new(move |result: &SearchResult| {
+            observed.lock().unwrap().push(result.clone());
+        }));
+
+        let result = bot.do_move(&board);
+        let stats = &result.stats;
+        assert!(stats.leaf_evaluations > 0 && stats.leaf_evaluations <= stats.nodes);
+        assert!(stats.tt_hits > 0);
+        assert!(stats.cutoffs.iter().sum::<u64>() > 0);
+        assert!(stats.cutoffs[0] >= stats.cutoffs[1]);
+        assert!(stats.branching_factor(result.depth) > 1.0);
+
+        // the observer sees every completed iteration, the last one is the result
+        let reported = reported.lock().unwrap();
+        let depths: Vec<u32> = reported.iter().map(|result| result.depth).collect();
+        assert_eq!((1..=5).collect::<Vec<u32>>(), depths);
+        for pair in reported.windows(2) {
+            assert!(pair[0].stats.nodes <= pair[1].stats.nodes);
+        }
+        assert_eq!(result.pv, reported.last().unwrap().pv);
+        assert_eq!(result.score, reported.last().unwrap().score);
+    }
+
+    struct DiscDifference;
+
+    impl Evaluator for DiscDifference {
+        fn evaluate(&self, board: &Board) -> i32 {
+            board.me().count_ones() as i32 - board.opp().count_ones() as i32
+        }
+    }
+
+    #[test]
+    fn test_set_evaluator() {
+        for board in generate_test_boards() {
+            if !board.has_moves() {
+                continue;
+            }
+
+            let mut bot = Bot::new(1, 1);
+            bot.set_evaluator(Arc::new(DiscDifference));
+
+            let best = board
+                .children()
+                .iter()
+                .map(|child| -DiscDifference.evaluate(child))
+                .max()
+                .unwrap();
+            assert_eq!(best, bot.do_move(&board).score);
+        }
+    }
+
+    #[test]
+    fn test_threads() {
+        for board in generate_test_boards() {
+            if board.count_empties() > 8 {
+                continue;
+            }
+
+            // searches reaching the end of the game find the exact score with any table content
+            let mut bot = Bot::new(64, 1);
+            bot.set_threads(4);
+            let exact = bot.alpha_beta(&board, -64000, 64000, 64);
+            assert_eq!(exact, bot.evaluate(&board));
+        }
+
+        let board = Board::new();
+        let mut bot = Bot::new(6, 1);
+        bot.set_threads(4);
+
+        let main_nodes = Arc::new(Mutex::new(0));
+        let observed = main_nodes.clone();
+        bot.set_observer(Box::new(move |result: &SearchResult| {
+            *observed.lock().unwrap() = result.stats.nodes;
+        }));
+
+        // the result counts the nodes of the helpers as well
+        let result = bot.search(&board, &Limits::depth(6));
+        assert!(board.children().contains(&result.board));
+        assert!(result.stats.nodes > *main_nodes.lock().unwrap());
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        for opening in ["f5d6c3d3c4f4c5b3", "f5f6e6f4", "f5d6c5f4e3"].iter() {
+            let board = parse_opening(opening).unwrap().board().clone();
+            let mut bot = Bot::new(7, 1);
+            let result = bot.search(&board, &Limits::depth(7));
+
+            // well ordered moves cause most cutoffs with the first move searched
+            assert!(result.stats.first_move_cutoffs() > 0.8);
+
+            // the search at the same depth finds the same score with an empty table
+            assert_eq!(Bot::new(7, 0).evaluate(&board), result.score);
+        }
+    }
 }
@@ -0,0 +1,503 @@
+use crate::board::Board;
+use crate::limits::Budget;
+use crate::transposition::{TranspositionTable, SOLVED_DEPTH};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WldResult {
+    Win,
+    Draw,
+    Loss,
+}
+
+impl WldResult {
+    pub fn from_score(score: i32) -> WldResult {
+        if score > 0 {
+            return WldResult::Win;
+        }
+        if score < 0 {
+            return WldResult::Loss;
+        }
+        WldResult::Draw
+    }
+}
+
+// Searches to the end of the game. Scores are final disc differences as returned
+// by Board::exact_score, results outside (alpha, beta) are clamped to the window.
+pub struct Solver {
+    nodes: u64,
+    budget: Budget,
+    aborted: bool,
+    // distance from the position passed to the outermost solve call
+    ply: usize,
+    // best line below every ply of the current line, passes are None
+    lines: Vec<Vec<Option<usize>>>,
+    table: Option<Arc<TranspositionTable>>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Solver {
+        Solver::with_budget(Budget::unlimited(), 0)
+    }
+
+    // Continues counting from nodes, so node limits cover the search that uses the solver.
+    pub fn with_budget(budget: Budget, nodes: u64) -> Solver {
+        Solver {
+            nodes,
+            budget,
+            aborted: false,
+            ply: 0,
+            lines: vec![Vec::new()],
+            table: None,
+        }
+    }
+
+    // Shares the bounds of positions with at least TABLE_EMPTIES empties with other solves
+    // using the table. Lines end early at positions the table already decides.
+    pub fn set_table(&mut self, table: Arc<TranspositionTable>) {
+        self.table = Some(table);
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // When the budget runs out the search stops and returned scores are meaningless.
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    // Best line found by the last solve. It only leads to the returned score when that
+    // score is inside the window.
+    pub fn line(&self) -> &[Option<usize>] {
+        &self.lines[0]
+    }
+
+    pub fn solve(&mut self, board: &Board, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+
+        if self.lines.len() <= self.ply + 1 {
+            self.lines.push(Vec::new());
+        }
+        self.lines[self.ply].clear();
+
+        if self.budget.poll(self.nodes) {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return alpha;
+        }
+
+        let empties = board.empties();
+        match empties.count_ones() {
+            0 => return clamp(board.exact_score(), alpha, beta),
+            1 => return clamp(self.solve_last(board, empties), alpha, beta),
+            2 | 3 => return self.solve_few(board, empties, alpha, beta),
+            _ => {}
+        }
+
+        let moves = board.moves();
+
+        if moves == 0 {
+            let mut passed = board.clone();
+            passed.switch_turn();
+            if !passed.has_moves() {
+                return clamp(board.exact_score(), alpha, beta);
+            }
+            let score = -self.solve_child(&passed, -beta, -alpha);
+            self.extend_line(None);
+            return score;
+        }
+
+        let mut hash_move = None;
+        if empties.count_ones() >= TABLE_EMPTIES {
+            if let Some(table) = &self.table {
+                if let Some(entry) = table.lookup(board) {
+                    if entry.depth == SOLVED_DEPTH {
+                        let (lower, upper) = (entry.lower / 1000, entry.upper / 1000);
+                        if lower >= beta {
+                            return beta;
+                        }
+                        if upper <= alpha {
+                            return alpha;
+                        }
+                        if lower == upper {
+                            return lower;
+                        }
+                        hash_move = entry.best_move;
+                    }
+                }
+            }
+        }
+
+        if empties.count_ones() >= STABILITY_EMPTIES {
+            // Stable discs of the opponent can never become ours, which bounds our score.
+            let stable = board.stable_discs();
+            if 64 - 2 * (stable & board.opp()).count_ones() as i32 <= alpha {
+                return alpha;
+            }
+            if 2 * (stable & board.me()).count_ones() as i32 - 64 >= beta {
+                return beta;
+            }
+        }
+
+        let mut children = ordered_children(board, moves);
+        if let Some(i) = children.iter().position(|(index, _)| Some(*index) == hash_move) {
+            children[..=i].rotate_right(1);
+        }
+
+        let original_alpha = alpha;
+        let mut best_move = None;
+        for (index, child) in children {
+            let score = -self.solve_child(&child, -beta, -alpha);
+            if self.aborted {
+                return alpha;
+            }
+            if score >= beta {
+                self.store(board, beta, 64, Some(index));
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                best_move = Some(index);
+                self.extend_line(Some(index));
+            }
+        }
+
+        if alpha > original_alpha {
+            self.store(board, alpha, alpha, best_move);
+        } else {
+            self.store(board, -64, alpha, None);
+        }
+        alpha
+    }
+
+    // Bounds are scaled like the search scores of the bots sharing the table.
+    fn store(&self, board: &Board, lower: i32, upper: i32, best_move: Option<usize>) {
+        if let Some(table) = &self.table {
+            if board.count_empties() >= TABLE_EMPTIES {
+                table.store(board, SOLVED_DEPTH, 1000 * lower, 1000 * upper, best_move);
+            }
+        }
+    }
+
+    fn solve_child(&mut self, child: &Board, alpha: i32, beta: i32) -> i32 {
+        self.ply += 1;
+        let score = self.solve(child, alpha, beta);
+        self.ply -= 1;
+        score
+    }
+
+    // The line of this ply becomes the move followed by the line of the child.
+    fn extend_line(&mut self, first: Option<usize>) {
+        let (lines, child_lines) = self.lines.split_at_mut(self.ply + 1);
+        let line = &mut lines[self.ply];
+        line.clear();
+        line.push(first);
+        line.extend_from_slice(&child_lines[0]);
+    }
+
+    // Final scores are always even, so the window (-1, 1) only proves the sign of the score.
+    pub fn solve_wld(&mut self, board: &Board) -> WldResult {
+        WldResult::from_score(self.solve(board, -1, 1))
+    }
+
+    // Two or three empty squares left: tries the empty squares directly instead of generating
+    // and sorting moves. With three empties the square alone in its quadrant goes first.
+    fn solve_few(&mut self, board: &Board, empties: u64, mut alpha: i32, beta: i32) -> i32 {
+        let parity = board.parity_squares();
+        let mut squares = [0; 3];
+        let mut count = 0;
+        for odd in [true, false].iter() {
+            let mut rest = empties;
+            while rest != 0 {
+                let index = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                if (parity & (1 << index) != 0) == *odd {
+                    squares[count] = index;
+                    count += 1;
+                }
+            }
+        }
+
+        let mut moved = false;
+        for index in squares[..count].iter() {
+            let flipped = board.flip(*index);
+            if flipped == 0 {
+                continue;
+            }
+            moved = true;
+
+            let child =
+                Board::from_bitboards(board.opp() ^ flipped, (board.me() ^ flipped) | (1 << index));
+            let score = -self.solve_child(&child, -beta, -alpha);
+            if self.aborted {
+                return alpha;
+            }
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+                self.extend_line(Some(*index));
+            }
+        }
+
+        if moved {
+            return alpha;
+        }
+
+        let mut passed = board.clone();
+        passed.switch_turn();
+        if squares[..count].iter().all(|index| passed.flip(*index) == 0) {
+            return clamp(board.exact_score(), alpha, beta);
+        }
+        let score = -self.solve_child(&passed, -beta, -alpha);
+        self.extend_line(None);
+        score
+    }
+
+    // One empty square left: count the flips instead of generating moves.
+    fn solve_last(&mut self, board: &Board, empty: u64) -> i32 {
+        let index = empty.trailing_zeros() as usize;
+        let me = board.me().count_ones() as i32;
+        let opp = board.opp().count_ones() as i32;
+
+        let flipped = board.flip(index).count_ones() as i32;
+        if flipped != 0 {
+            self.nodes += 1;
+            self.lines[self.ply].push(Some(index));
+            return (me + flipped + 1) - (opp - flipped);
+        }
+
+        let mut passed = board.clone();
+        passed.switch_turn();
+        let flipped = passed.flip(index).count_ones() as i32;
+        if flipped != 0 {
+            self.nodes += 1;
+            self.lines[self.ply].extend_from_slice(&[None, Some(index)]);
+            return (me - flipped) - (opp + flipped + 1);
+        }
+
+        board.exact_score()
+    }
+}
+
+// Above this many empties moves are sorted by the mobility they leave the opponent.
+const FASTEST_FIRST_EMPTIES: u32 = 5;
+
+// Up to this many empties moves into odd empty regions are preferred.
+const PARITY_EMPTIES: u32 = 12;
+
+// Below this many empties the table is not used, looking positions up costs more than
+// solving them again.
+const TABLE_EMPTIES: u32 = 10;
+
+// Below this many empties computing stable discs costs more than the cutoffs save.
+const STABILITY_EMPTIES: u32 = 8;
+
+// Children in search order: moves leaving the opponent few replies come first. Close to
+// the end of the game, moves into odd empty regions are preferred.
+fn ordered_children(board: &Board, mut moves: u64) -> Vec<(usize, Board)> {
+    let empties = board.count_empties();
+    let parity = if empties <= PARITY_EMPTIES {
+        board.parity_squares()
+    } else {
+        0
+    };
+    let mut children = Vec::with_capacity(moves.count_ones() as usize);
+
+    while moves != 0 {
+        let index = moves.trailing_zeros() as usize;
+        moves &= moves - 1;
+        children.push((index, board.do_move(index)));
+    }
+
+    if empties > FASTEST_FIRST_EMPTIES {
+        children.sort_by_key(|(index, child)| {
+            (child.moves().count_ones(), parity & (1 << index) == 0)
+        });
+    } else {
+        children.sort_by_key(|(index, _)| parity & (1 << index) == 0);
+    }
+    children
+}
+
+fn clamp(score: i32, alpha: i32, beta: i32) -> i32 {
+    if score <= alpha {
+        return alpha;
+    }
+    if score >= beta {
+        return beta;
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Solver, WldResult};
+    use crate::board::tests::generate_test_boards;
+    use crate::board::Board;
+    use crate::limits::{Budget, Limits, StopHandle};
+    use crate::transposition::TranspositionTable;
+    use crate::xot::parse_opening;
+    use std::sync::Arc;
+
+    fn naive_solve(board: &Board) -> i32 {
+        let children = board.children();
+
+        if children.is_empty() {
+            let mut passed = board.clone();
+            passed.switch_turn();
+            if !passed.has_moves() {
+                return board.exact_score();
+            }
+            return -naive_solve(&passed);
+        }
+
+        children.iter().map(|child| -naive_solve(child)).max().unwrap()
+    }
+
+    fn endgame_test_boards() -> Vec<Board> {
+        generate_test_boards()
+            .into_iter()
+            .filter(|board| board.count_empties() <= 8)
+            .collect()
+    }
+
+    #[test]
+    fn test_solve() {
+        let mut solver = Solver::new();
+
+        for board in endgame_test_boards() {
+            assert_eq!(naive_solve(&board), solver.solve(&board, -64, 64));
+        }
+    }
+
+    #[test]
+    fn test_solve_line() {
+        let mut solver = Solver::new();
+
+        for board in endgame_test_boards() {
+            let score = solver.solve(&board, -65, 65);
+
+            // the line is legal and ends the game with the solved score
+            let mut current = board.clone();
+            let mut sign = 1;
+            for played in solver.line().to_vec() {
+                match played {
+                    Some(index) => {
+                        assert!(current.moves() & (1 << index) != 0);
+                        current = current.do_move(index);
+                    }
+                    None => {
+                        assert!(!current.has_moves());
+                        current.switch_turn();
+                    }
+                }
+                sign = -sign;
+            }
+
+            let mut passed = current.clone();
+            passed.switch_turn();
+            assert!(!current.has_moves() && !passed.has_moves());
+            assert_eq!(score, sign * current.exact_score());
+        }
+    }
+
+    #[test]
+    fn test_solve_few_empties() {
+        let mut solver = Solver::new();
+
+        // play on to two and three empty squares, which have their own solver
+        for board in endgame_test_boards() {
+            let mut board = board;
+            while board.count_empties() > 3 && board.has_moves() {
+                board = board.do_random_move();
+            }
+
+            let exact = naive_solve(&board);
+            assert_eq!(exact, solver.solve(&board, -64, 64));
+            for alpha in [-64, -10, -1, 0, 2, 20].iter() {
+                let beta = alpha + 3;
+                assert_eq!(exact.max(*alpha).min(beta), solver.solve(&board, *alpha, beta));
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_window() {
+        let mut solver = Solver::new();
+
+        for board in endgame_test_boards() {
+            let exact = naive_solve(&board);
+            for alpha in [-64, -10, -1, 0, 2, 20].iter() {
+                let beta = alpha + 3;
+                let score = solver.solve(&board, *alpha, beta);
+                assert_eq!(exact.max(*alpha).min(beta), score);
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_table() {
+        let boards = generate_test_boards();
+        let table = Arc::new(TranspositionTable::new(1));
+
+        // bounds from earlier windows are reused by later solves of the same positions
+        for board in boards.iter().filter(|board| board.count_empties() == 12) {
+            let exact = Solver::new().solve(board, -64, 64);
+            for alpha in [-64, -10, -1, 0, 2, 20].iter() {
+                let beta = alpha + 3;
+                let mut solver = Solver::new();
+                solver.set_table(table.clone());
+                assert_eq!(exact.max(*alpha).min(beta), solver.solve(board, *alpha, beta));
+            }
+
+            let mut solver = Solver::new();
+            solver.set_table(table.clone());
+            assert_eq!(exact, solver.solve(board, -64, 64));
+        }
+    }
+
+    #[test]
+    fn test_solve_wld() {
+        let mut exact_solver = Solver::new();
+        let mut wld_solver = Solver::new();
+
+        for board in endgame_test_boards() {
+            let exact = exact_solver.solve(&board, -64, 64);
+            assert_eq!(WldResult::from_score(exact), wld_solver.solve_wld(&board));
+        }
+
+        assert!(wld_solver.nodes() <= exact_solver.nodes());
+    }
+
+    #[test]
+    fn test_solve_aborted() {
+        let limits = Limits {
+            nodes: Some(1000),
+            ..Limits::default()
+        };
+        let board = parse_opening("f5d6c3d3c4f4c5b3").unwrap().board().clone();
+
+        let budget = Budget::new(&limits, 20, &StopHandle::new());
+        let mut solver = Solver::with_budget(budget, 0);
+        solver.solve(&board, -64, 64);
+        assert!(solver.aborted());
+        assert!(solver.nodes() <= 2048);
+    }
+
+    #[test]
+    fn test_exact_scores_even() {
+        for board in endgame_test_boards() {
+            assert_eq!(0, naive_solve(&board) % 2);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::board::Board;
+
+// Scores a position for the player to move, in the units of the search where a disc
+// at the end of the game is worth 1000. Scores must stay within -64000 and 64000.
+pub trait Evaluator: Send + Sync {
+    fn evaluate(&self, board: &Board) -> i32;
+}
+
+// Counts corners and the empty squares next to the discs of each player.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SimpleEvaluator;
+
+impl Evaluator for SimpleEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        5 * board.corner_difference() + board.potential_moves_difference()
+    }
+}
+
+// The simple evaluation with discs that can never be flipped added.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StabilityEvaluator;
+
+impl Evaluator for StabilityEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        SimpleEvaluator.evaluate(board) + 2 * board.stable_discs_difference()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluator, SimpleEvaluator, StabilityEvaluator};
+    use crate::board::tests::generate_test_boards;
+    use crate::board::Board;
+
+    #[test]
+    fn test_simple_evaluator() {
+        assert_eq!(0, SimpleEvaluator.evaluate(&Board::new()));
+
+        for board in generate_test_boards() {
+            let swapped = Board::from_bitboards(board.opp(), board.me());
+            assert_eq!(-SimpleEvaluator.evaluate(&board), SimpleEvaluator.evaluate(&swapped));
+        }
+    }
+
+    #[test]
+    fn test_stability_evaluator() {
+        assert_eq!(0, StabilityEvaluator.evaluate(&Board::new()));
+
+        for board in generate_test_boards() {
+            let score = StabilityEvaluator.evaluate(&board);
+            let stable = 2 * board.stable_discs_difference();
+            assert_eq!(SimpleEvaluator.evaluate(&board) + stable, score);
+
+            let swapped = Board::from_bitboards(board.opp(), board.me());
+            assert_eq!(-score, StabilityEvaluator.evaluate(&swapped));
+        }
+    }
+}
@@ -0,0 +1,15 @@
+pub mod bits;
+pub mod board;
+pub mod book;
+pub mod bot;
+pub mod endgame;
+pub mod evaluation;
+pub mod limits;
+pub mod moves;
+pub mod pattern;
+pub mod player;
+pub mod pool;
+pub mod position;
+pub mod training;
+pub mod transposition;
+pub mod xot;
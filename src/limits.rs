@@ -0,0 +1,206 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    // maximum iterative deepening depth
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    // fixed time for this move
+    pub move_time: Option<Duration>,
+    // remaining time on the game clock and the time added after every move
+    pub time_left: Option<Duration>,
+    pub increment: Duration,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Limits {
+        Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        }
+    }
+
+    pub fn move_time(move_time: Duration) -> Limits {
+        Limits {
+            move_time: Some(move_time),
+            ..Limits::default()
+        }
+    }
+
+    // Spreads the game clock over the moves we still have to play, every one of
+    // them also gets the increment. Never uses more than half of the clock.
+    pub fn move_budget(&self, empties: u32) -> Option<Duration> {
+        let clock_budget = self.time_left.map(|time_left| {
+            let moves_left = empties.div_ceil(2);
+            let budget = time_left / moves_left.max(1) + self.increment;
+            budget.min(time_left / 2)
+        });
+
+        match (self.move_time, clock_budget) {
+            (Some(move_time), Some(clock_budget)) => Some(move_time.min(clock_budget)),
+            (move_time, clock_budget) => move_time.or(clock_budget),
+        }
+    }
+}
+
+// Shared flag to stop a search running on another thread.
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    pub fn new() -> StopHandle {
+        StopHandle::default()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    pub fn clear(&self) {
+        self.stopped.store(false, Ordering::Relaxed);
+    }
+}
+
+// Limits of a running search, polled by the search while it visits nodes.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    start: Instant,
+    deadline: Option<Instant>,
+    max_nodes: Option<u64>,
+    stop: StopHandle,
+    next_poll: u64,
+}
+
+impl Budget {
+    pub fn new(limits: &Limits, empties: u32, stop: &StopHandle) -> Budget {
+        let start = Instant::now();
+        Budget {
+            start,
+            deadline: limits.move_budget(empties).map(|budget| start + budget),
+            max_nodes: limits.nodes,
+            stop: stop.clone(),
+            next_poll: 0,
+        }
+    }
+
+    pub fn unlimited() -> Budget {
+        Budget::new(&Limits::default(), 0, &StopHandle::new())
+    }
+
+    pub fn exceeded(&self, nodes: u64) -> bool {
+        if self.stop.is_stopped() {
+            return true;
+        }
+        if let Some(max_nodes) = self.max_nodes {
+            if nodes >= max_nodes {
+                return true;
+            }
+        }
+        match self.deadline {
+            Some(deadline) => Instant::now() >= deadline,
+            None => false,
+        }
+    }
+
+    // Cheap enough to call for every node, the limits are only checked every 1024 nodes.
+    pub fn poll(&mut self, nodes: u64) -> bool {
+        if nodes < self.next_poll {
+            return false;
+        }
+        self.next_poll = nodes + 1024;
+        self.exceeded(nodes)
+    }
+
+    // A new iteration takes longer than all previous ones together, so only
+    // start one when less than half of the time is used.
+    pub fn allows_iteration(&self) -> bool {
+        match self.deadline {
+            Some(deadline) => Instant::now() < self.start + (deadline - self.start) / 2,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Budget, Limits, StopHandle};
+    use std::time::Duration;
+
+    #[test]
+    fn test_move_budget() {
+        assert_eq!(None, Limits::default().move_budget(60));
+        assert_eq!(None, Limits::depth(5).move_budget(60));
+
+        let move_time = Duration::from_millis(500);
+        assert_eq!(Some(move_time), Limits::move_time(move_time).move_budget(60));
+
+        let mut limits = Limits {
+            time_left: Some(Duration::from_secs(60)),
+            ..Limits::default()
+        };
+        assert_eq!(Some(Duration::from_secs(2)), limits.move_budget(60));
+        assert_eq!(Some(Duration::from_secs(30)), limits.move_budget(1));
+
+        limits.increment = Duration::from_secs(1);
+        assert_eq!(Some(Duration::from_secs(3)), limits.move_budget(60));
+
+        limits.move_time = Some(move_time);
+        assert_eq!(Some(move_time), limits.move_budget(60));
+    }
+
+    #[test]
+    fn test_budget() {
+        let budget = Budget::unlimited();
+        assert!(!budget.exceeded(u64::MAX));
+        assert!(budget.allows_iteration());
+
+        let limits = Limits {
+            nodes: Some(100),
+            ..Limits::default()
+        };
+        let budget = Budget::new(&limits, 60, &StopHandle::new());
+        assert!(!budget.exceeded(99));
+        assert!(budget.exceeded(100));
+
+        let limits = Limits::move_time(Duration::from_secs(0));
+        let budget = Budget::new(&limits, 60, &StopHandle::new());
+        assert!(budget.exceeded(0));
+        assert!(!budget.allows_iteration());
+    }
+
+    #[test]
+    fn test_budget_stop() {
+        let stop = StopHandle::new();
+        let budget = Budget::new(&Limits::default(), 60, &stop);
+        assert!(!budget.exceeded(0));
+
+        stop.clone().stop();
+        assert!(budget.exceeded(0));
+
+        stop.clear();
+        assert!(!budget.exceeded(0));
+    }
+
+    #[test]
+    fn test_budget_poll() {
+        let limits = Limits {
+            nodes: Some(1500),
+            ..Limits::default()
+        };
+        let mut budget = Budget::new(&limits, 60, &StopHandle::new());
+
+        assert!(!budget.poll(0));
+        assert!(!budget.poll(1499));
+        // not checked again until 1024 nodes later
+        assert!(!budget.poll(1600));
+        assert!(budget.poll(2600));
+    }
+}
@@ -1,25 +1,272 @@
-mod bits;
-mod board;
-mod bot;
+use othello_bot::{board, book, bot, evaluation, limits, pattern, player, pool, position, xot};
+use player::Player;
+use std::env;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
-fn main() {
-    let mut board = board::Board::new();
-    let mut bot = bot::Bot::new(9);
-
-    let mut turn = false;
-    board.print(turn);
-
-    loop {
-        if !board.has_moves() {
-            board.switch_turn();
-            turn = !turn;
-            if !board.has_moves() {
-                break;
+const WEIGHTS_PATH: &str = "weights.bin";
+
+// Number of book roots expanded together in one batch of evaluations.
+const BATCH_ROOTS: usize = 256;
+
+// Time between saves while generating a book, saving rewrites the whole file.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+fn print_progress(result: &bot::SearchResult) {
+    let stats = &result.stats;
+    println!(
+        "Depth {:2}: {:6} | {:9} nodes in {:4.2} sec = {:9} nodes/sec | bf {:4.2} | first cut {:3.0}% | {}",
+        result.depth,
+        result.score,
+        stats.nodes,
+        stats.elapsed.as_secs_f32(),
+        stats.nodes_per_second() as u64,
+        stats.branching_factor(result.depth),
+        100.0 * stats.first_move_cutoffs(),
+        bot::format_pv(&result.pv)
+    );
+}
+
+// Bots evaluate with pattern weights when they are found in the working directory.
+fn load_weights(bot: &mut bot::Bot) {
+    let path = Path::new(WEIGHTS_PATH);
+    if path.exists() {
+        let weights = pattern::Weights::load(path).expect("Could not load weights");
+        bot.set_evaluator(Arc::new(weights));
+    }
+}
+
+// Searches use every core.
+fn thread_count() -> usize {
+    thread::available_parallelism().map_or(1, |threads| threads.get())
+}
+
+fn new_bot(search_depth: u32, book_path: Option<&Path>) -> bot::Bot {
+    let mut bot = bot::Bot::new(search_depth, 64);
+    bot.set_endgame_empties(12);
+    bot.set_threads(thread_count());
+    load_weights(&mut bot);
+
+    if let Some(path) = book_path {
+        bot.set_book(book::Book::load(path).expect("Could not load book"));
+    }
+    bot
+}
+
+fn new_player(name: &str, search_depth: u32) -> Option<Box<dyn Player>> {
+    let player: Box<dyn Player> = match name {
+        "bot" => {
+            let mut bot = new_bot(search_depth, None);
+            bot.set_observer(Box::new(print_progress));
+            Box::new(bot)
+        }
+        // ignores the pattern weights, to compare evaluations
+        "simple" => {
+            let mut bot = new_bot(search_depth, None);
+            bot.set_evaluator(Arc::new(evaluation::SimpleEvaluator));
+            bot.set_observer(Box::new(print_progress));
+            Box::new(bot)
+        }
+        // the simple evaluation with stable discs
+        "stable" => {
+            let mut bot = new_bot(search_depth, None);
+            bot.set_evaluator(Arc::new(evaluation::StabilityEvaluator));
+            bot.set_observer(Box::new(print_progress));
+            Box::new(bot)
+        }
+        "random" => Box::new(player::RandomPlayer),
+        "greedy" => Box::new(player::GreedyPlayer),
+        "human" => Box::new(player::HumanPlayer::new(io::BufReader::new(io::stdin()))),
+        _ => return None,
+    };
+    Some(player)
+}
+
+// Plays until the game is over, black moves first unless the position says otherwise.
+fn play_game(
+    black: &mut dyn Player,
+    white: &mut dyn Player,
+    mut position: position::Position,
+    limits: &limits::Limits,
+    verbose: bool,
+) -> position::Position {
+    if verbose {
+        position.print();
+    }
+
+    while !position.is_game_over() {
+        let result = if position.white_to_move() {
+            white.choose_move(&position, limits)
+        } else {
+            black.choose_move(&position, limits)
+        };
+        position = position.play(result.best_move).unwrap();
+
+        if verbose {
+            println!("Played {} with score {}", result.best_move, result.score);
+            position.print();
+        }
+    }
+    position
+}
+
+fn play(book_path: Option<&Path>) {
+    let mut black = new_bot(9, book_path);
+    let mut white = new_bot(9, book_path);
+    black.set_observer(Box::new(print_progress));
+    white.set_observer(Box::new(print_progress));
+
+    let limits = limits::Limits::depth(9);
+    play_game(&mut black, &mut white, position::Position::new(), &limits, true);
+}
+
+fn play_players(black: &str, white: &str, search_depth: u32) {
+    let players = (new_player(black, search_depth), new_player(white, search_depth));
+    let (mut black, mut white) = match players {
+        (Some(black), Some(white)) => (black, white),
+        _ => {
+            eprintln!("Players are bot, simple, stable, random, greedy or human");
+            return;
+        }
+    };
+
+    let limits = limits::Limits::depth(search_depth);
+    let position =
+        play_game(&mut *black, &mut *white, position::Position::new(), &limits, true);
+    println!("Final score for black: {:+}", position.black_score());
+}
+
+// Plays a self-play game from every opening and reports the final disc difference for black.
+fn play_xot(path: &Path, search_depth: u32) {
+    let openings = xot::load(path).expect("Could not load openings");
+    let mut black = new_bot(search_depth, None);
+    let mut white = new_bot(search_depth, None);
+    let limits = limits::Limits::depth(search_depth);
+    let mut total = 0;
+
+    for (i, opening) in openings.iter().enumerate() {
+        let position = play_game(&mut black, &mut white, opening.clone(), &limits, false);
+
+        let black_score = position.black_score();
+        total += black_score;
+        println!("Opening {:5}/{:5}: black {:+3}", i + 1, openings.len(), black_score);
+    }
+
+    println!("Total for black: {:+}", total);
+}
+
+fn analyze(position: &position::Position, search_depth: u32) {
+    let mut bot = bot::Bot::new(search_depth, 256);
+    bot.set_endgame_empties(16);
+    load_weights(&mut bot);
+
+    position.print();
+    let limits = limits::Limits::depth(search_depth);
+
+    for result in bot.analyze(position.board(), &limits, true) {
+        println!("{}: {:6} | {}", result.pv[0], result.score, bot::format_pv(&result.pv));
+    }
+}
+
+fn generate_book(path: &Path, search_depth: u32, xot_path: Option<&Path>) {
+    let mut book = if path.exists() {
+        book::Book::load(path).expect("Could not load book")
+    } else {
+        book::Book::new()
+    };
+    // every worker searches single threaded with its own share of the table memory
+    let workers = thread_count();
+    let mut pool = pool::BotPool::new(workers, || {
+        let mut bot = bot::Bot::new(search_depth, (256 / workers).max(16));
+        bot.set_endgame_empties(16);
+        load_weights(&mut bot);
+        bot
+    });
+
+    let mut roots = match xot_path {
+        Some(xot_path) => xot::load(xot_path)
+            .expect("Could not load openings")
+            .iter()
+            .map(|opening| opening.board().clone())
+            .collect(),
+        None => vec![board::Board::new()],
+    };
+
+    println!("Loaded {} positions from {}", book.len(), path.display());
+
+    let mut batches = 0;
+    let mut last_save = Instant::now();
+    while !roots.is_empty() {
+        let mut open = Vec::new();
+
+        // expand below many roots at once to keep all workers busy, dropping fully explored roots
+        for chunk in roots.chunks(BATCH_ROOTS) {
+            let expanded = book.expand_roots(chunk, &mut pool);
+            open.extend(
+                chunk
+                    .iter()
+                    .zip(expanded)
+                    .filter(|(_, expanded)| *expanded)
+                    .map(|(root, _)| root.clone()),
+            );
+
+            batches += 1;
+            if last_save.elapsed() >= SAVE_INTERVAL {
+                book.save(path).expect("Could not save book");
+                last_save = Instant::now();
+                println!(
+                    "{:6} batches | {:9} positions | {:5} open roots",
+                    batches,
+                    book.len(),
+                    roots.len()
+                );
             }
         }
 
-        board = bot.do_move(&board);
-        turn = !turn;
-        board.print(turn);
+        roots = open;
+    }
+
+    book.save(path).expect("Could not save book");
+    println!("Book complete with {} positions", book.len());
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+
+    let parse_depth = |arg: Option<&String>| match arg {
+        Some(depth) => depth.parse().expect("Invalid search depth"),
+        None => 9,
+    };
+
+    match args.get(1).map(|arg| arg.as_str()) {
+        None => play(None),
+        Some("play") => play(args.get(2).map(Path::new)),
+        Some("game") => match (args.get(2), args.get(3)) {
+            (Some(black), Some(white)) => play_players(black, white, parse_depth(args.get(4))),
+            _ => eprintln!("Usage: othello_bot game <black> <white> [depth]"),
+        },
+        Some("book") => {
+            let path = args.get(2).map(|arg| arg.as_str()).unwrap_or("book.bin");
+            let xot_path = args.get(4).map(Path::new);
+            generate_book(Path::new(path), parse_depth(args.get(3)), xot_path)
+        }
+        Some("xot") => match args.get(2) {
+            Some(path) => play_xot(Path::new(path), parse_depth(args.get(3))),
+            None => eprintln!("Usage: othello_bot xot <openings> [depth]"),
+        },
+        Some("analyze") => match args.get(2).map(|arg| arg.parse::<position::Position>()) {
+            Some(Ok(position)) => analyze(&position, parse_depth(args.get(3))),
+            Some(Err(error)) => eprintln!("{}", error),
+            None => eprintln!("Usage: othello_bot analyze <position> [depth]"),
+        },
+        Some(command) => {
+            eprintln!("Unknown command: {}", command);
+            eprintln!(
+                "Usage: othello_bot [play [book] | game <black> <white> [depth] | book [file] [depth] [openings] | xot <openings> [depth] | analyze <position> [depth]]"
+            );
+        }
     }
 }
@@ -0,0 +1,187 @@
+use crate::position::Position;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Square(usize),
+    Pass,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseMoveError {
+    InvalidMove(String),
+    IllegalMove { ply: usize, played: Move },
+}
+
+impl fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseMoveError::InvalidMove(text) => write!(f, "Invalid move: {}", text),
+            ParseMoveError::IllegalMove { ply, played } => {
+                write!(f, "Illegal move {} at ply {}", played, ply + 1)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Square(index) => write!(
+                f,
+                "{}{}",
+                (b'a' + (index % 8) as u8) as char,
+                (b'1' + (index / 8) as u8) as char
+            ),
+            Move::Pass => write!(f, "PS"),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(text: &str) -> Result<Move, ParseMoveError> {
+        let lower = text.trim().to_ascii_lowercase();
+
+        match lower.as_str() {
+            "ps" | "pa" | "pass" | "--" => return Ok(Move::Pass),
+            _ => {}
+        }
+
+        let bytes = lower.as_bytes();
+        if bytes.len() == 2 && (b'a'..=b'h').contains(&bytes[0]) && (b'1'..=b'8').contains(&bytes[1])
+        {
+            let x = (bytes[0] - b'a') as usize;
+            let y = (bytes[1] - b'1') as usize;
+            return Ok(Move::Square(8 * y + x));
+        }
+
+        Err(ParseMoveError::InvalidMove(text.to_string()))
+    }
+}
+
+// Splits a transcript like "f5d6c3d3c4" into moves. Whitespace between moves is ignored.
+pub fn parse_transcript(transcript: &str) -> Result<Vec<Move>, ParseMoveError> {
+    let chars: Vec<char> = transcript.chars().filter(|c| !c.is_whitespace()).collect();
+
+    chars
+        .chunks(2)
+        .map(|chunk| chunk.iter().collect::<String>().parse())
+        .collect()
+}
+
+pub fn format_transcript(moves: &[Move]) -> String {
+    moves.iter().map(|played| played.to_string()).collect()
+}
+
+// Plays moves from position, passing automatically when the side to move has no moves.
+pub fn replay(position: &Position, moves: &[Move]) -> Result<Position, ParseMoveError> {
+    let mut position = position.clone();
+
+    for (ply, played) in moves.iter().enumerate() {
+        if *played != Move::Pass && position.legal_moves() == vec![Move::Pass] {
+            position = position.play(Move::Pass).unwrap();
+        }
+
+        position = match position.play(*played) {
+            Some(child) => child,
+            None => {
+                return Err(ParseMoveError::IllegalMove {
+                    ply,
+                    played: *played,
+                })
+            }
+        };
+    }
+
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_transcript, parse_transcript, replay, Move, ParseMoveError};
+    use crate::board::Board;
+    use crate::position::Position;
+
+    #[test]
+    fn test_move_parse_format() {
+        for index in 0..64 {
+            let text = Move::Square(index).to_string();
+            assert_eq!(Ok(Move::Square(index)), text.parse());
+            assert_eq!(Ok(Move::Square(index)), text.to_uppercase().parse());
+        }
+
+        assert_eq!("a1", Move::Square(0).to_string());
+        assert_eq!("h1", Move::Square(7).to_string());
+        assert_eq!("a8", Move::Square(56).to_string());
+        assert_eq!("f5", Move::Square(37).to_string());
+        assert_eq!("PS", Move::Pass.to_string());
+
+        for pass in ["ps", "PA", "pass", "--"].iter() {
+            assert_eq!(Ok(Move::Pass), pass.parse());
+        }
+
+        for invalid in ["", "a", "a0", "a9", "i1", "a1b", "11"].iter() {
+            assert!(invalid.parse::<Move>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_transcript() {
+        let moves = parse_transcript("f5d6C3 d3c4").unwrap();
+        assert_eq!(moves.len(), 5);
+        assert_eq!(moves[0], Move::Square(37));
+        assert_eq!("f5d6c3d3c4", format_transcript(&moves));
+
+        assert_eq!(Ok(vec![]), parse_transcript(""));
+        assert!(parse_transcript("f5d").is_err());
+        assert!(parse_transcript("f5z9").is_err());
+    }
+
+    #[test]
+    fn test_replay() {
+        let start = Position::new();
+
+        let position = replay(&start, &parse_transcript("f5").unwrap()).unwrap();
+        assert_eq!(*position.board(), Board::new().do_move(37));
+        assert!(position.white_to_move());
+
+        let position = replay(&start, &parse_transcript("f5d6c3d3c4f4c5b3").unwrap()).unwrap();
+        assert_eq!(position.board().count_discs(), 12);
+        assert!(!position.white_to_move());
+
+        assert_eq!(
+            replay(&start, &parse_transcript("f5f5").unwrap()),
+            Err(ParseMoveError::IllegalMove {
+                ply: 1,
+                played: Move::Square(37)
+            })
+        );
+        assert!(replay(&start, &[Move::Pass]).is_err());
+    }
+
+    #[test]
+    fn test_replay_passes() {
+        // random games replay the same with and without explicit passes
+        for _ in 0..20 {
+            let mut position = Position::new();
+            let mut moves = Vec::new();
+            while !position.is_game_over() {
+                let legal = position.legal_moves();
+                let played = legal[rand::random::<usize>() % legal.len()];
+                moves.push(played);
+                position = position.play(played).unwrap();
+            }
+
+            let explicit = replay(&Position::new(), &moves).unwrap();
+            assert_eq!(explicit, position);
+
+            let implicit: Vec<Move> = moves.iter().cloned().filter(|m| *m != Move::Pass).collect();
+            let implicit = replay(&Position::new(), &implicit).unwrap();
+            assert_eq!(explicit.board(), implicit.board());
+            assert_eq!(explicit.white_to_move(), implicit.white_to_move());
+        }
+    }
+}
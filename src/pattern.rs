@@ -0,0 +1,360 @@
+use crate::board::{Board, Transform};
+use crate::evaluation::{Evaluator, SimpleEvaluator};
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"OTHEVAL\0";
+const VERSION: u32 = 2;
+
+// Every pattern is listed once, the evaluation also uses all its rotations and mirrors.
+// Squares use the board indices, so 0 is a1 and 9 is b2.
+const PATTERNS: [&[usize]; 10] = [
+    // edge with both X-squares
+    &[0, 1, 2, 3, 4, 5, 6, 7, 9, 14],
+    // corner 3x3
+    &[0, 1, 2, 8, 9, 10, 16, 17, 18],
+    // second, third and fourth row
+    &[8, 9, 10, 11, 12, 13, 14, 15],
+    &[16, 17, 18, 19, 20, 21, 22, 23],
+    &[24, 25, 26, 27, 28, 29, 30, 31],
+    // diagonals of length 8 down to 4
+    &[0, 9, 18, 27, 36, 45, 54, 63],
+    &[1, 10, 19, 28, 37, 46, 55],
+    &[2, 11, 20, 29, 38, 47],
+    &[3, 12, 21, 30, 39],
+    &[4, 13, 22, 31],
+];
+
+// Squares in the largest pattern.
+const MAX_PATTERN_SQUARES: usize = 10;
+
+// Positions with the same number of empty squares divided by this share their weights.
+const PHASE_EMPTIES: u32 = 4;
+pub const PHASES: usize = (60 / PHASE_EMPTIES as usize) + 1;
+
+// Evaluation scores are in the units of the search, 1000 per disc.
+const MAX_SCORE: i32 = 63999;
+
+// Number of distinct rotations and mirrors of all patterns.
+const INSTANCES: usize = 76;
+
+// Squares of one rotated or mirrored pattern, kept inline so evaluating does not allocate.
+#[derive(Debug, Clone, Copy, Default)]
+struct Instance {
+    pattern: usize,
+    squares: [usize; MAX_PATTERN_SQUARES],
+    len: usize,
+}
+
+impl Instance {
+    // Squares count 0 when empty, 1 for the player to move and 2 for the opponent.
+    fn index(&self, me: u64, opp: u64) -> usize {
+        self.squares[..self.len].iter().fold(0, |index, square| {
+            3 * index + ((me >> square) & 1) as usize + 2 * ((opp >> square) & 1) as usize
+        })
+    }
+}
+
+pub struct Weights {
+    // every rotated and mirrored pattern
+    instances: [Instance; INSTANCES],
+    // weights by phase and pattern, indexed by the state of the pattern squares in base 3
+    tables: Vec<Vec<Vec<i16>>>,
+    // phases without training samples, they use the simple evaluation instead
+    untrained: [bool; PHASES],
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights::new()
+    }
+}
+
+impl Weights {
+    pub fn new() -> Weights {
+        let mut instances = [Instance::default(); INSTANCES];
+        let mut count = 0;
+
+        for (pattern, squares) in PATTERNS.iter().enumerate() {
+            let mut seen: Vec<[usize; MAX_PATTERN_SQUARES]> = Vec::new();
+
+            for transform in Transform::all() {
+                let mut transformed = [0; MAX_PATTERN_SQUARES];
+                for (i, square) in squares.iter().enumerate() {
+                    transformed[i] = transform.apply_move(*square);
+                }
+
+                // Symmetric patterns keep both orders of their squares, so weights that differ
+                // between the orders still evaluate all symmetries alike. Transforms that keep
+                // the order would count the pattern twice.
+                if !seen.contains(&transformed) {
+                    seen.push(transformed);
+                    instances[count] = Instance {
+                        pattern,
+                        squares: transformed,
+                        len: squares.len(),
+                    };
+                    count += 1;
+                }
+            }
+        }
+
+        assert_eq!(INSTANCES, count);
+
+        let tables = (0..PHASES)
+            .map(|_| Weights::table_sizes().into_iter().map(|size| vec![0; size]).collect())
+            .collect();
+
+        Weights {
+            instances,
+            tables,
+            untrained: [false; PHASES],
+        }
+    }
+
+    // Number of weights of every pattern within one phase.
+    pub fn table_sizes() -> Vec<usize> {
+        PATTERNS
+            .iter()
+            .map(|squares| 3usize.pow(squares.len() as u32))
+            .collect()
+    }
+
+    pub fn phase(board: &Board) -> usize {
+        (board.count_empties() / PHASE_EMPTIES) as usize
+    }
+
+    // Pattern and table index of every pattern instance.
+    pub fn indices(&self, board: &Board) -> Vec<(usize, usize)> {
+        let (me, opp) = (board.me(), board.opp());
+
+        self.instances
+            .iter()
+            .map(|instance| (instance.pattern, instance.index(me, opp)))
+            .collect()
+    }
+
+    pub fn weight_mut(&mut self, phase: usize, pattern: usize, index: usize) -> &mut i16 {
+        &mut self.tables[phase][pattern][index]
+    }
+
+    pub fn set_untrained(&mut self, phase: usize) {
+        self.untrained[phase] = true;
+    }
+
+    pub fn is_trained(&self, phase: usize) -> bool {
+        !self.untrained[phase]
+    }
+
+    // File layout: magic, version, number of phases and patterns, one byte per phase that
+    // is 1 for trained phases, followed by the tables of every phase in pattern order.
+    // Weights are little endian i16.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp_path = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(PHASES as u32).to_le_bytes())?;
+        writer.write_all(&(PATTERNS.len() as u32).to_le_bytes())?;
+        for untrained in self.untrained.iter() {
+            writer.write_all(&[!untrained as u8])?;
+        }
+
+        for table in self.tables.iter().flatten() {
+            for weight in table.iter() {
+                writer.write_all(&weight.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Weights> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let header_size = MAGIC.len() + 4 + 4 + 4;
+        if bytes.len() < header_size || &bytes[0..8] != MAGIC {
+            return Err(invalid("Not a weights file"));
+        }
+        if u32::from_le_bytes(bytes[8..12].try_into().unwrap()) != VERSION {
+            return Err(invalid("Unsupported weights version"));
+        }
+        let phases = u32::from_le_bytes(bytes[12..16].try_into().unwrap()) as usize;
+        let patterns = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        if phases != PHASES || patterns != PATTERNS.len() {
+            return Err(invalid("Weights use different patterns"));
+        }
+
+        let mut weights = Weights::new();
+        let size: usize = weights.tables.iter().flatten().map(|table| table.len()).sum();
+        let tables_start = header_size + PHASES;
+        if bytes.len() != tables_start + 2 * size {
+            return Err(invalid("Truncated weights"));
+        }
+
+        for (phase, trained) in bytes[header_size..tables_start].iter().enumerate() {
+            weights.untrained[phase] = *trained == 0;
+        }
+
+        let mut chunks = bytes[tables_start..].chunks(2);
+        for table in weights.tables.iter_mut().flatten() {
+            for weight in table.iter_mut() {
+                *weight = i16::from_le_bytes(chunks.next().unwrap().try_into().unwrap());
+            }
+        }
+
+        Ok(weights)
+    }
+}
+
+impl Evaluator for Weights {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let phase = Weights::phase(board);
+        if self.untrained[phase] {
+            return SimpleEvaluator.evaluate(board);
+        }
+
+        let tables = &self.tables[phase];
+        let (me, opp) = (board.me(), board.opp());
+
+        let mut score = 0;
+        for instance in self.instances.iter() {
+            score += tables[instance.pattern][instance.index(me, opp)] as i32;
+        }
+        score.clamp(-MAX_SCORE, MAX_SCORE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Weights, PATTERNS, PHASES};
+    use crate::board::tests::generate_test_boards;
+    use crate::board::{Board, Transform};
+    use crate::evaluation::{Evaluator, SimpleEvaluator};
+    use rand::Rng;
+    use std::env;
+
+    // Every weight is the number of own discs minus opponent discs in the pattern.
+    fn disc_count_weights() -> Weights {
+        let mut weights = Weights::new();
+        for phase in 0..PHASES {
+            for (pattern, squares) in PATTERNS.iter().enumerate() {
+                for index in 0..3usize.pow(squares.len() as u32) {
+                    let mut count = 0;
+                    let mut rest = index;
+                    for _ in 0..squares.len() {
+                        count += match rest % 3 {
+                            1 => 1,
+                            2 => -1,
+                            _ => 0,
+                        };
+                        rest /= 3;
+                    }
+                    *weights.weight_mut(phase, pattern, index) = count;
+                }
+            }
+        }
+        weights
+    }
+
+    fn naive_evaluate(weights: &Weights, board: &Board) -> i32 {
+        let mut score = 0;
+        for instance in weights.instances.iter() {
+            for square in instance.squares[..instance.len].iter() {
+                if board.me() & (1 << square) != 0 {
+                    score += 1;
+                }
+                if board.opp() & (1 << square) != 0 {
+                    score -= 1;
+                }
+            }
+        }
+        score
+    }
+
+    #[test]
+    fn test_instances() {
+        let weights = Weights::new();
+        let count = |pattern| {
+            let instances = weights.instances.iter();
+            instances.filter(|instance| instance.pattern == pattern).count()
+        };
+
+        assert_eq!(8, count(0));
+        assert_eq!(8, count(1));
+        assert_eq!(8, count(2));
+        assert_eq!(4, count(5));
+        assert_eq!(8, count(6));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let weights = disc_count_weights();
+        assert_eq!(0, Weights::new().evaluate(&Board::new()));
+
+        for board in generate_test_boards() {
+            let score = weights.evaluate(&board);
+            assert_eq!(naive_evaluate(&weights, &board), score);
+
+            // patterns cover every symmetry and the weights do not favor a side
+            for transform in Transform::all() {
+                assert_eq!(score, weights.evaluate(&board.transform(transform)));
+            }
+            let swapped = Board::from_bitboards(board.opp(), board.me());
+            assert_eq!(-score, weights.evaluate(&swapped));
+        }
+
+        // phases without training samples fall back to the simple evaluation
+        let mut weights = disc_count_weights();
+        let board = Board::new();
+        weights.set_untrained(Weights::phase(&board));
+        assert_eq!(SimpleEvaluator.evaluate(&board), weights.evaluate(&board));
+    }
+
+    #[test]
+    fn test_evaluate_random_weights() {
+        let mut weights = Weights::new();
+        let mut rng = rand::thread_rng();
+        for phase in 0..PHASES {
+            for (pattern, size) in Weights::table_sizes().into_iter().enumerate() {
+                for index in 0..size {
+                    *weights.weight_mut(phase, pattern, index) = rng.gen_range(-100, 100);
+                }
+            }
+        }
+
+        // the order of the squares within a pattern makes no difference either
+        for board in generate_test_boards() {
+            let score = weights.evaluate(&board);
+            for transform in Transform::all() {
+                assert_eq!(score, weights.evaluate(&board.transform(transform)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut weights = disc_count_weights();
+        weights.set_untrained(0);
+
+        let path = env::temp_dir().join("othello_bot_test_weights_save_load.bin");
+        weights.save(&path).unwrap();
+        let loaded = Weights::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(weights.tables == loaded.tables);
+        assert!(!loaded.is_trained(0) && loaded.is_trained(1));
+        for board in generate_test_boards() {
+            assert_eq!(weights.evaluate(&board), loaded.evaluate(&board));
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use crate::board::Board;
+use crate::bot::{Bot, SearchResult, SearchStats};
+use crate::limits::Limits;
+use crate::moves::Move;
+use crate::position::Position;
+use std::io::{self, BufRead, Write};
+
+pub trait Player {
+    // Picks a legal move, which is a pass when the player to move is stuck.
+    // Panics when the game is over.
+    fn choose_move(&mut self, position: &Position, limits: &Limits) -> SearchResult;
+}
+
+impl Player for Bot {
+    fn choose_move(&mut self, position: &Position, limits: &Limits) -> SearchResult {
+        self.search(position.board(), limits)
+    }
+}
+
+// Result for players that do not search, scores are left at 0.
+fn chosen(position: &Position, played: Move) -> SearchResult {
+    let board = match position.play(played) {
+        Some(child) => child.board().clone(),
+        None => panic!("Illegal move"),
+    };
+
+    SearchResult {
+        best_move: played,
+        board,
+        pv: vec![played],
+        score: 0,
+        depth: 0,
+        stats: SearchStats::default(),
+    }
+}
+
+fn forced_pass(position: &Position) -> Option<Move> {
+    if position.is_game_over() {
+        panic!("Game is over");
+    }
+    if position.board().has_moves() {
+        return None;
+    }
+    Some(Move::Pass)
+}
+
+pub struct RandomPlayer;
+
+impl Player for RandomPlayer {
+    fn choose_move(&mut self, position: &Position, _limits: &Limits) -> SearchResult {
+        if let Some(pass) = forced_pass(position) {
+            return chosen(position, pass);
+        }
+
+        let board = position.board();
+        let child = board.do_random_move();
+        let (index, _) = board
+            .children_with_moves()
+            .into_iter()
+            .find(|(_, other)| *other == child)
+            .unwrap();
+        chosen(position, Move::Square(index))
+    }
+}
+
+// Flips as many discs as possible, ties go to the lowest square.
+pub struct GreedyPlayer;
+
+impl GreedyPlayer {
+    fn discs_after(child: &Board) -> u32 {
+        child.opp().count_ones()
+    }
+}
+
+impl Player for GreedyPlayer {
+    fn choose_move(&mut self, position: &Position, _limits: &Limits) -> SearchResult {
+        if let Some(pass) = forced_pass(position) {
+            return chosen(position, pass);
+        }
+
+        let mut best = None;
+        for (index, child) in position.board().children_with_moves() {
+            let discs = GreedyPlayer::discs_after(&child);
+            match best {
+                Some((_, best_discs)) if best_discs >= discs => {}
+                _ => best = Some((index, discs)),
+            }
+        }
+        chosen(position, Move::Square(best.unwrap().0))
+    }
+}
+
+// Reads moves like "f5" from the input until a legal one is entered.
+pub struct HumanPlayer<R: BufRead> {
+    input: R,
+}
+
+impl<R: BufRead> HumanPlayer<R> {
+    pub fn new(input: R) -> Self {
+        HumanPlayer { input }
+    }
+}
+
+impl<R: BufRead> Player for HumanPlayer<R> {
+    fn choose_move(&mut self, position: &Position, _limits: &Limits) -> SearchResult {
+        if let Some(pass) = forced_pass(position) {
+            println!("No moves, passing");
+            return chosen(position, pass);
+        }
+
+        loop {
+            print!("Your move: ");
+            io::stdout().flush().unwrap();
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap() == 0 {
+                panic!("No more input");
+            }
+
+            match line.parse::<Move>() {
+                Ok(played) if position.is_legal(played) => return chosen(position, played),
+                _ => {
+                    let legal: Vec<String> =
+                        position.legal_moves().iter().map(|m| m.to_string()).collect();
+                    println!("Invalid move, choose from {}", legal.join(" "));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GreedyPlayer, HumanPlayer, Player, RandomPlayer};
+    use crate::board::tests::generate_test_boards;
+    use crate::bot::Bot;
+    use crate::limits::Limits;
+    use crate::moves::Move;
+    use crate::position::Position;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_players_legal() {
+        let limits = Limits::depth(2);
+        let mut players: Vec<Box<dyn Player>> = vec![
+            Box::new(Bot::new(2, 1)),
+            Box::new(RandomPlayer),
+            Box::new(GreedyPlayer),
+        ];
+
+        for board in generate_test_boards() {
+            let position = Position::from_board(board, false);
+            if position.is_game_over() {
+                continue;
+            }
+
+            for player in players.iter_mut() {
+                let result = player.choose_move(&position, &limits);
+                assert!(position.is_legal(result.best_move));
+                assert_eq!(position.play(result.best_move).unwrap().board(), &result.board);
+            }
+        }
+    }
+
+    #[test]
+    fn test_greedy_player() {
+        for board in generate_test_boards() {
+            if !board.has_moves() {
+                continue;
+            }
+
+            let position = Position::from_board(board.clone(), false);
+            let result = GreedyPlayer.choose_move(&position, &Limits::default());
+            let most = board
+                .children()
+                .iter()
+                .map(|child| child.opp().count_ones())
+                .max()
+                .unwrap();
+            assert_eq!(most, result.board.opp().count_ones());
+        }
+    }
+
+    #[test]
+    fn test_human_player() {
+        let position = Position::new();
+        let mut human = HumanPlayer::new(Cursor::new("z9\na1\n  F5 \n"));
+
+        let result = human.choose_move(&position, &Limits::default());
+        assert_eq!("f5".parse::<Move>().unwrap(), result.best_move);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::board::Board;
+use crate::bot::Bot;
+use crate::limits::Limits;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+// Worker bots that score independent positions in parallel. Every worker keeps its
+// own bot, including the transposition table, between batches.
+pub struct BotPool {
+    bots: Vec<Bot>,
+    search_depth: u32,
+}
+
+impl BotPool {
+    pub fn new<F>(workers: usize, create: F) -> BotPool
+    where
+        F: Fn() -> Bot,
+    {
+        let bots: Vec<Bot> = (0..workers.max(1)).map(|_| create()).collect();
+        let search_depth = bots[0].search_depth();
+        BotPool { bots, search_depth }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.bots.len()
+    }
+
+    pub fn search_depth(&self) -> u32 {
+        self.search_depth
+    }
+
+    // Scores every board for the player to move, in the order of the boards. Boards are
+    // handed out to the workers one at a time, so slow positions do not hold up the others.
+    // Progress is called on this thread with the number of scored boards and the total.
+    pub fn evaluate<F>(&mut self, boards: &[Board], limits: &Limits, mut progress: F) -> Vec<i32>
+    where
+        F: FnMut(usize, usize),
+    {
+        let boards = Arc::new(boards.to_vec());
+        let next = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let handles: Vec<_> = self
+            .bots
+            .drain(..)
+            .map(|mut bot| {
+                let boards = boards.clone();
+                let next = next.clone();
+                let sender = sender.clone();
+                let limits = limits.clone();
+
+                thread::spawn(move || {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        if index >= boards.len() {
+                            break;
+                        }
+                        let score = BotPool::score(&mut bot, &boards[index], &limits);
+                        sender.send((index, score)).unwrap();
+                    }
+                    bot
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut scores = vec![0; boards.len()];
+        for (done, (index, score)) in receiver.iter().enumerate() {
+            scores[index] = score;
+            progress(done + 1, boards.len());
+        }
+
+        self.bots = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+        scores
+    }
+
+    fn score(bot: &mut Bot, board: &Board, limits: &Limits) -> i32 {
+        let mut passed = board.clone();
+        passed.switch_turn();
+        if !board.has_moves() && !passed.has_moves() {
+            return 1000 * board.exact_score();
+        }
+        bot.search(board, limits).score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BotPool;
+    use crate::board::tests::generate_test_boards;
+    use crate::bot::Bot;
+    use crate::limits::Limits;
+
+    #[test]
+    fn test_pool_evaluate() {
+        let boards = generate_test_boards();
+        let depth = 3;
+
+        // without transposition tables the scores do not depend on earlier searches
+        let mut pool = BotPool::new(3, || Bot::new(depth, 0));
+        assert_eq!(3, pool.workers());
+        assert_eq!(depth, pool.search_depth());
+
+        let mut reported = Vec::new();
+        let scores = pool.evaluate(&boards, &Limits::depth(depth), |done, total| {
+            reported.push((done, total))
+        });
+
+        assert_eq!(boards.len(), scores.len());
+        for (board, score) in boards.iter().zip(scores.iter()) {
+            assert_eq!(Bot::new(depth, 0).evaluate(board), *score);
+        }
+
+        let expected: Vec<_> = (1..=boards.len()).map(|done| (done, boards.len())).collect();
+        assert_eq!(expected, reported);
+
+        // the workers are kept for the next batch
+        assert_eq!(3, pool.workers());
+        assert!(pool.evaluate(&[], &Limits::depth(depth), |_, _| {}).is_empty());
+    }
+}
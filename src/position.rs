@@ -0,0 +1,339 @@
+use crate::board::Board;
+use crate::moves::Move;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Position {
+    board: Board,
+    white_to_move: bool,
+    passes: u32,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position::new()
+    }
+}
+
+impl Position {
+    pub fn new() -> Position {
+        Position::from_board(Board::new(), false)
+    }
+
+    pub fn from_board(board: Board, white_to_move: bool) -> Position {
+        Position {
+            board,
+            white_to_move,
+            passes: 0,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn white_to_move(&self) -> bool {
+        self.white_to_move
+    }
+
+    // Number of passes played in a row just before this position.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        if self.board.has_moves() {
+            return false;
+        }
+        let mut passed = self.board.clone();
+        passed.switch_turn();
+        !passed.has_moves()
+    }
+
+    // A pass is the only legal move when the player to move is stuck but the game is not over.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.board.moves();
+        let mut legal = Vec::new();
+
+        while moves != 0 {
+            let index = moves.trailing_zeros() as usize;
+            legal.push(Move::Square(index));
+            moves &= !(1 << index);
+        }
+
+        if legal.is_empty() && !self.is_game_over() {
+            legal.push(Move::Pass);
+        }
+        legal
+    }
+
+    pub fn is_legal(&self, played: Move) -> bool {
+        match played {
+            Move::Square(index) => index < 64 && (self.board.moves() >> index) & 1 == 1,
+            Move::Pass => !self.board.has_moves() && !self.is_game_over(),
+        }
+    }
+
+    pub fn play(&self, played: Move) -> Option<Position> {
+        if !self.is_legal(played) {
+            return None;
+        }
+
+        let (board, passes) = match played {
+            Move::Square(index) => (self.board.do_move(index), 0),
+            Move::Pass => {
+                let mut board = self.board.clone();
+                board.switch_turn();
+                (board, self.passes + 1)
+            }
+        };
+
+        Some(Position {
+            board,
+            white_to_move: !self.white_to_move,
+            passes,
+        })
+    }
+
+    pub fn children(&self) -> Vec<(Move, Position)> {
+        self.legal_moves()
+            .into_iter()
+            .map(|played| (played, self.play(played).unwrap()))
+            .collect()
+    }
+
+    // Final disc difference seen from black, using the same scoring as Board::exact_score.
+    pub fn black_score(&self) -> i32 {
+        if self.white_to_move {
+            -self.board.exact_score()
+        } else {
+            self.board.exact_score()
+        }
+    }
+
+    pub fn print(&self) {
+        self.board.print(self.white_to_move);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsePositionError {
+    InvalidLength(usize),
+    InvalidSquare(char),
+    InvalidSide(char),
+}
+
+impl fmt::Display for ParsePositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParsePositionError::InvalidLength(length) => {
+                write!(f, "Expected 65 characters, found {}", length)
+            }
+            ParsePositionError::InvalidSquare(c) => write!(f, "Invalid square: {}", c),
+            ParsePositionError::InvalidSide(c) => write!(f, "Invalid side to move: {}", c),
+        }
+    }
+}
+
+// 64 squares from a1 to h8 using 'X' for black, 'O' for white and '-' for empty,
+// followed by a space and the colour to move, for example the start position:
+// ---------------------------OX------XO--------------------------- X
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (black, white) = if self.white_to_move {
+            (self.board.opp(), self.board.me())
+        } else {
+            (self.board.me(), self.board.opp())
+        };
+
+        for index in 0..64 {
+            let square = match ((black >> index) & 1, (white >> index) & 1) {
+                (1, _) => 'X',
+                (_, 1) => 'O',
+                _ => '-',
+            };
+            write!(f, "{}", square)?;
+        }
+
+        write!(f, " {}", if self.white_to_move { 'O' } else { 'X' })
+    }
+}
+
+// Also accepts '*' for black, '.' for empty, lowercase letters and any whitespace.
+impl FromStr for Position {
+    type Err = ParsePositionError;
+
+    fn from_str(text: &str) -> Result<Position, ParsePositionError> {
+        let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if chars.len() != 65 {
+            return Err(ParsePositionError::InvalidLength(chars.len()));
+        }
+
+        let mut black = 0u64;
+        let mut white = 0u64;
+
+        for (index, c) in chars[..64].iter().enumerate() {
+            match c {
+                'X' | 'x' | '*' => black |= 1 << index,
+                'O' | 'o' => white |= 1 << index,
+                '-' | '.' => {}
+                _ => return Err(ParsePositionError::InvalidSquare(*c)),
+            }
+        }
+
+        let white_to_move = match chars[64] {
+            'X' | 'x' | '*' => false,
+            'O' | 'o' => true,
+            c => return Err(ParsePositionError::InvalidSide(c)),
+        };
+
+        let board = if white_to_move {
+            Board::from_bitboards(white, black)
+        } else {
+            Board::from_bitboards(black, white)
+        };
+
+        Ok(Position::from_board(board, white_to_move))
+    }
+}
+
+// Parses every line of a text file, skipping empty lines and lines starting with '#'.
+// Errors name the line they were found on.
+pub fn load_lines<T, E, F>(path: &Path, parse: F) -> io::Result<Vec<T>>
+where
+    E: fmt::Display,
+    F: Fn(&str) -> Result<T, E>,
+{
+    let content = fs::read_to_string(path)?;
+    let mut items = Vec::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse(line) {
+            Ok(item) => items.push(item),
+            Err(error) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Line {}: {}", number + 1, error),
+                ))
+            }
+        }
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParsePositionError, Position};
+    use crate::board::tests::generate_test_boards;
+    use crate::moves::Move;
+
+    #[test]
+    fn test_position_new() {
+        let position = Position::new();
+        assert!(!position.white_to_move());
+        assert_eq!(position.passes(), 0);
+        assert!(!position.is_game_over());
+        assert_eq!(position.legal_moves().len(), 4);
+        assert_eq!(position.black_score(), 0);
+    }
+
+    #[test]
+    fn test_position_legal_moves() {
+        for board in generate_test_boards() {
+            let position = Position::from_board(board.clone(), false);
+            let legal = position.legal_moves();
+
+            if board.has_moves() {
+                assert_eq!(legal.len(), board.children().len());
+            } else if position.is_game_over() {
+                assert!(legal.is_empty());
+            } else {
+                assert_eq!(legal, vec![Move::Pass]);
+            }
+
+            for played in legal {
+                assert!(position.is_legal(played));
+                let child = position.play(played).unwrap();
+                assert!(child.white_to_move());
+            }
+
+            for index in 0..64 {
+                let played = Move::Square(index);
+                assert_eq!(position.is_legal(played), position.play(played).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn test_position_passes() {
+        for board in generate_test_boards() {
+            let position = Position::from_board(board, true);
+            if position.legal_moves() != vec![Move::Pass] {
+                continue;
+            }
+
+            let passed = position.play(Move::Pass).unwrap();
+            assert_eq!(passed.passes(), 1);
+            assert!(!passed.white_to_move());
+            assert!(passed.play(Move::Pass).is_none());
+            assert_eq!(passed.black_score(), position.black_score());
+
+            let (_, child) = &passed.children()[0];
+            assert_eq!(child.passes(), 0);
+        }
+    }
+
+    #[test]
+    fn test_position_to_string() {
+        assert_eq!(
+            Position::new().to_string(),
+            "---------------------------OX------XO--------------------------- X"
+        );
+
+        let position = Position::new().play(Move::Square(37)).unwrap();
+        assert_eq!(
+            position.to_string(),
+            "---------------------------OX------XXX-------------------------- O"
+        );
+    }
+
+    #[test]
+    fn test_position_from_str() {
+        for board in generate_test_boards() {
+            for white_to_move in [false, true].iter() {
+                let position = Position::from_board(board.clone(), *white_to_move);
+                assert_eq!(Ok(position.clone()), position.to_string().parse());
+            }
+        }
+
+        let spaced = "-------- -------- -------- ---ox--- ---xo--- -------- -------- --------\n*";
+        assert_eq!(Ok(Position::new()), spaced.parse());
+        assert_eq!(
+            Ok(Position::new()),
+            "...........................OX......XO........................... x".parse()
+        );
+
+        assert_eq!(
+            Err(ParsePositionError::InvalidLength(64)),
+            "---------------------------OX------XO---------------------------".parse::<Position>()
+        );
+        assert_eq!(
+            Err(ParsePositionError::InvalidSquare('?')),
+            "?--------------------------OX------XO--------------------------- X".parse::<Position>()
+        );
+        assert_eq!(
+            Err(ParsePositionError::InvalidSide('-')),
+            "---------------------------OX------XO--------------------------- -".parse::<Position>()
+        );
+    }
+}
@@ -0,0 +1,222 @@
+use crate::board::Board;
+use crate::endgame::Solver;
+use crate::pattern::{Weights, PHASES};
+use crate::position::{load_lines, ParsePositionError, Position};
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    pub board: Board,
+    // final disc difference for the player to move
+    pub score: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseSampleError {
+    MissingScore,
+    InvalidScore(String),
+    InvalidPosition(ParsePositionError),
+}
+
+impl fmt::Display for ParseSampleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseSampleError::MissingScore => write!(f, "Missing score"),
+            ParseSampleError::InvalidScore(score) => write!(f, "Invalid score: {}", score),
+            ParseSampleError::InvalidPosition(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+// A position in the text format of Position followed by the score, for example:
+// ---------------------------OX------XO--------------------------- X 0
+pub fn parse_sample(line: &str) -> Result<Sample, ParseSampleError> {
+    let line = line.trim();
+    let split = line.rfind(char::is_whitespace).ok_or(ParseSampleError::MissingScore)?;
+    let (position, score) = line.split_at(split);
+
+    let score = score.trim();
+    let score = score
+        .parse()
+        .map_err(|_| ParseSampleError::InvalidScore(score.to_string()))?;
+    let position: Position = position.parse().map_err(ParseSampleError::InvalidPosition)?;
+
+    Ok(Sample {
+        board: position.board().clone(),
+        score,
+    })
+}
+
+pub fn format_sample(sample: &Sample) -> String {
+    let position = Position::from_board(sample.board.clone(), false);
+    format!("{} {}", position, sample.score)
+}
+
+// Reads one sample per line, skipping empty lines and lines starting with '#'.
+pub fn load_samples(path: &Path) -> io::Result<Vec<Sample>> {
+    load_lines(path, parse_sample)
+}
+
+// Random positions scored by solving them, with every number of empty squares in the
+// range in turn.
+pub fn generate_samples(count: usize, empties: RangeInclusive<u32>) -> Vec<Sample> {
+    let mut solver = Solver::new();
+    let numbers: Vec<u32> = empties.collect();
+
+    (0..count)
+        .map(|i| {
+            let board = Board::new_random(64 - numbers[i % numbers.len()]);
+            let score = solver.solve(&board, -64, 64);
+            Sample { board, score }
+        })
+        .collect()
+}
+
+// phase, pattern indices and target score of a sample
+type Features = (usize, Vec<(usize, usize)>, f64);
+
+// Fits the weights of every phase to the samples of that phase with gradient descent.
+// Every epoch moves each weight by the average error of the samples it appears in,
+// spread over all pattern instances. Reports the root mean squared error in discs.
+// Phases without samples are marked untrained.
+pub fn fit<F>(samples: &[Sample], epochs: u32, mut report: F) -> Weights
+where
+    F: FnMut(u32, f64),
+{
+    let mut weights = Weights::new();
+    let sizes = Weights::table_sizes();
+    let zeros = || -> Vec<Vec<f64>> { sizes.iter().map(|size| vec![0.0; *size]).collect() };
+
+    let features: Vec<Features> = samples
+        .iter()
+        .map(|sample| {
+            let board = &sample.board;
+            (Weights::phase(board), weights.indices(board), sample.score as f64)
+        })
+        .collect();
+
+    let instances = weights.indices(&Board::new()).len() as f64;
+    let mut tables: Vec<Vec<Vec<f64>>> = (0..PHASES).map(|_| zeros()).collect();
+
+    for epoch in 0..epochs {
+        let mut errors: Vec<Vec<Vec<f64>>> = (0..PHASES).map(|_| zeros()).collect();
+        let mut counts: Vec<Vec<Vec<f64>>> = (0..PHASES).map(|_| zeros()).collect();
+        let mut squared = 0.0;
+
+        for (phase, indices, target) in features.iter() {
+            let table = &tables[*phase];
+            let predicted: f64 = indices.iter().map(|(p, i)| table[*p][*i]).sum();
+            let error = target - predicted;
+            squared += error * error;
+
+            for (p, i) in indices.iter() {
+                errors[*phase][*p][*i] += error;
+                counts[*phase][*p][*i] += 1.0;
+            }
+        }
+
+        for (phase, table) in tables.iter_mut().enumerate() {
+            for (p, weights) in table.iter_mut().enumerate() {
+                for (i, weight) in weights.iter_mut().enumerate() {
+                    let count = counts[phase][p][i];
+                    if count > 0.0 {
+                        *weight += errors[phase][p][i] / (count * instances);
+                    }
+                }
+            }
+        }
+
+        report(epoch, (squared / features.len().max(1) as f64).sqrt());
+    }
+
+    // the search uses 1000 per disc
+    for (phase, table) in tables.iter().enumerate() {
+        for (p, values) in table.iter().enumerate() {
+            for (i, value) in values.iter().enumerate() {
+                let scaled = (1000.0 * value).round();
+                *weights.weight_mut(phase, p, i) =
+                    scaled.max(i16::MIN as f64).min(i16::MAX as f64) as i16;
+            }
+        }
+    }
+
+    for phase in 0..PHASES {
+        if !features.iter().any(|(sample_phase, _, _)| *sample_phase == phase) {
+            weights.set_untrained(phase);
+        }
+    }
+
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fit, format_sample, generate_samples, load_samples, parse_sample};
+    use super::{ParseSampleError, Sample};
+    use crate::board::Board;
+    use crate::evaluation::Evaluator;
+    use crate::pattern::{Weights, PHASES};
+    use std::env;
+    use std::fs;
+    use std::slice;
+
+    #[test]
+    fn test_parse_sample() {
+        let line = "---------------------------OX------XO--------------------------- X -4";
+        let sample = parse_sample(line).unwrap();
+        assert_eq!(Board::new(), sample.board);
+        assert_eq!(-4, sample.score);
+        assert_eq!(line, format_sample(&sample));
+
+        assert_eq!(Err(ParseSampleError::MissingScore), parse_sample("X"));
+        assert!(parse_sample(&line.replace("-4", "x")).is_err());
+        assert!(parse_sample("---- X 3").is_err());
+
+        let path = env::temp_dir().join("othello_bot_test_load_samples.txt");
+        fs::write(&path, format!("# samples\n{}\n\n{} 1\n", line, line)).unwrap();
+        let error = load_samples(&path).unwrap_err();
+        assert!(error.to_string().starts_with("Line 4"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_generate_samples() {
+        let samples = generate_samples(20, 3..=6);
+        for sample in samples.iter() {
+            assert!((3..=6).contains(&sample.board.count_empties()));
+            assert!(sample.score.abs() <= 64);
+        }
+        for empties in 3..=6 {
+            assert!(samples.iter().any(|sample| sample.board.count_empties() == empties));
+        }
+    }
+
+    #[test]
+    fn test_fit() {
+        let samples = generate_samples(200, 6..=6);
+
+        let mut errors = Vec::new();
+        let weights = fit(&samples, 20, |_, error| errors.push(error));
+        assert_eq!(20, errors.len());
+        assert!(errors.last().unwrap() < &errors[0]);
+
+        // a single sample is learned up to rounding of every weight
+        let sample = Sample {
+            board: Board::new(),
+            score: 10,
+        };
+        let weights_single = fit(slice::from_ref(&sample), 30, |_, _| {});
+        let error = weights_single.evaluate(&sample.board) - 10000;
+        assert!(error.abs() <= 50);
+
+        assert!(samples.iter().any(|sample| weights.evaluate(&sample.board) != 0));
+
+        // only the phase of the samples is trained
+        for phase in 0..PHASES {
+            assert_eq!(phase == Weights::phase(&samples[0].board), weights.is_trained(phase));
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use crate::board::Board;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub depth: u32,
+    pub lower: i32,
+    pub upper: i32,
+    pub best_move: Option<usize>,
+}
+
+// Entries are stored in four words: both bitboards xor-ed with a mix of the two data
+// words, followed by the data words. Entries torn by writes from another thread, even
+// writes of the same board, no longer match their board, so the table can be shared
+// between threads without locking.
+type Slot = [AtomicU64; 4];
+
+// Depth of entries for solved positions, their bounds hold at any search depth.
+pub const SOLVED_DEPTH: u32 = u32::MAX;
+
+const OCCUPIED: u64 = 1 << 40;
+const NO_MOVE: u64 = 0xFF;
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> Self {
+        let size = (megabytes << 20) / mem::size_of::<Slot>();
+        TranspositionTable {
+            slots: (0..size).map(|_| Default::default()).collect(),
+        }
+    }
+
+    fn slot(&self, board: &Board) -> Option<&Slot> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        Some(&self.slots[(board.hash_key() % self.slots.len() as u64) as usize])
+    }
+
+    pub fn lookup(&self, board: &Board) -> Option<Entry> {
+        let slot = self.slot(board)?;
+        let me = slot[0].load(Ordering::Relaxed);
+        let opp = slot[1].load(Ordering::Relaxed);
+        let bounds = slot[2].load(Ordering::Relaxed);
+        let info = slot[3].load(Ordering::Relaxed);
+
+        if info & OCCUPIED == 0
+            || me ^ bounds ^ info.rotate_left(32) != board.me()
+            || opp ^ info ^ bounds.rotate_left(32) != board.opp()
+        {
+            return None;
+        }
+
+        let best_move = match (info >> 32) & 0xFF {
+            NO_MOVE => None,
+            index => Some(index as usize),
+        };
+
+        Some(Entry {
+            depth: info as u32,
+            lower: bounds as u32 as i32,
+            upper: (bounds >> 32) as u32 as i32,
+            best_move,
+        })
+    }
+
+    pub fn store(&self, board: &Board, depth: u32, lower: i32, upper: i32, best_move: Option<usize>) {
+        let slot = match self.slot(board) {
+            Some(slot) => slot,
+            None => return,
+        };
+
+        // Keep deeper results for the same position, always replace other positions.
+        if let Some(entry) = self.lookup(board) {
+            if entry.depth > depth {
+                return;
+            }
+        }
+
+        let bounds = (lower as u32 as u64) | ((upper as u32 as u64) << 32);
+        let best_move = best_move.map_or(NO_MOVE, |index| index as u64);
+        let info = OCCUPIED | (best_move << 32) | depth as u64;
+
+        slot[0].store(board.me() ^ bounds ^ info.rotate_left(32), Ordering::Relaxed);
+        slot[1].store(board.opp() ^ info ^ bounds.rotate_left(32), Ordering::Relaxed);
+        slot[2].store(bounds, Ordering::Relaxed);
+        slot[3].store(info, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranspositionTable;
+    use crate::board::tests::generate_test_boards;
+    use crate::board::Board;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_transposition_table_store_lookup() {
+        let table = TranspositionTable::new(1);
+
+        let board = Board::new();
+        assert!(table.lookup(&board).is_none());
+
+        table.store(&board, 3, -5, 7, Some(19));
+        let entry = table.lookup(&board).unwrap();
+        assert_eq!(entry.depth, 3);
+        assert_eq!(entry.lower, -5);
+        assert_eq!(entry.upper, 7);
+        assert_eq!(entry.best_move, Some(19));
+
+        // shallower results do not overwrite deeper ones
+        table.store(&board, 2, 0, 0, None);
+        assert_eq!(table.lookup(&board).unwrap().depth, 3);
+
+        table.store(&board, 4, 1, 1, Some(26));
+        assert_eq!(table.lookup(&board).unwrap().depth, 4);
+    }
+
+    #[test]
+    fn test_transposition_table_no_false_hits() {
+        let table = TranspositionTable::new(1);
+        let boards = generate_test_boards();
+
+        for (i, board) in boards.iter().enumerate() {
+            table.store(board, i as u32, 0, 0, None);
+        }
+
+        // colliding boards overwrite each other, so a hit must carry its own board's data,
+        // which for repeated boards is the last one stored
+        for board in boards.iter() {
+            if let Some(entry) = table.lookup(board) {
+                let last = boards.iter().rposition(|other| other == board).unwrap();
+                assert_eq!(entry.depth, last as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transposition_table_disabled() {
+        let table = TranspositionTable::new(0);
+        let board = Board::new();
+        table.store(&board, 1, 0, 0, None);
+        assert!(table.lookup(&board).is_none());
+    }
+
+    #[test]
+    fn test_transposition_table_threads() {
+        let table = Arc::new(TranspositionTable::new(1));
+        let boards = Arc::new(generate_test_boards());
+
+        // every thread stores the same values for a board, so any hit must have them
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let table = table.clone();
+                let boards = boards.clone();
+                thread::spawn(move || {
+                    for _ in 0..20 {
+                        for board in boards.iter() {
+                            let score = board.count_discs() as i32;
+                            table.store(board, 1, -score, score, Some(score as usize));
+                            if let Some(entry) = table.lookup(board) {
+                                assert_eq!(entry.lower, -score);
+                                assert_eq!(entry.upper, score);
+                                assert_eq!(entry.best_move, Some(score as usize));
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_transposition_table_no_torn_entries() {
+        let table = Arc::new(TranspositionTable::new(1));
+        let board = Board::new();
+
+        // threads store different results for the same board, a hit must not mix them
+        let handles: Vec<_> = (1..5)
+            .map(|depth| {
+                let table = table.clone();
+                let board = board.clone();
+                thread::spawn(move || {
+                    for _ in 0..10000 {
+                        let score = 1000 * depth as i32;
+                        table.store(&board, depth, -score, score, Some(depth as usize));
+                        if let Some(entry) = table.lookup(&board) {
+                            let score = 1000 * entry.depth as i32;
+                            assert_eq!(entry.lower, -score);
+                            assert_eq!(entry.upper, score);
+                            assert_eq!(entry.best_move, Some(entry.depth as usize));
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::moves::{parse_transcript, replay, ParseMoveError};
+use crate::position::{load_lines, Position};
+use std::io;
+use std::path::Path;
+
+// Replays a move list like "f5d6c3d3c4f4c5b3" from the start position. The returned
+// position keeps track of the player to move, which is white after an odd number of moves.
+pub fn parse_opening(line: &str) -> Result<Position, ParseMoveError> {
+    let moves = parse_transcript(line)?;
+    replay(&Position::new(), &moves)
+}
+
+// Reads one opening per line, skipping empty lines and lines starting with '#'.
+pub fn load(path: &Path) -> io::Result<Vec<Position>> {
+    load_lines(path, parse_opening)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, parse_opening};
+    use crate::position::Position;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_parse_opening() {
+        assert_eq!(parse_opening(""), Ok(Position::new()));
+
+        let position = parse_opening("f5d6c3d3c4f4c5b3").unwrap();
+        assert_eq!(position.board().count_discs(), 12);
+        assert!(!position.white_to_move());
+        assert_eq!(position, parse_opening(" F5D6C3D3C4F4C5B3\n").unwrap());
+
+        // after an odd number of moves white is to move
+        let position = parse_opening("f5d6c3").unwrap();
+        assert!(position.white_to_move());
+        assert_eq!(60, position.black_score());
+
+        assert!(parse_opening("f5d6c").is_err());
+        assert!(parse_opening("f5z9").is_err());
+        assert!(parse_opening("f5f5").is_err());
+        assert!(parse_opening("a1").is_err());
+    }
+
+    #[test]
+    fn test_load() {
+        let path = env::temp_dir().join("othello_bot_test_xot_load.txt");
+
+        fs::write(&path, "# openings\nf5d6c3d3c4f4c5b3\n\nf5f6e6f4\n").unwrap();
+        let openings = load(&path).unwrap();
+        assert_eq!(openings.len(), 2);
+        assert_eq!(openings[1], parse_opening("f5f6e6f4").unwrap());
+
+        fs::write(&path, "f5d6\nf5f5\n").unwrap();
+        let error = load(&path).unwrap_err();
+        assert!(error.to_string().starts_with("Line 2"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}